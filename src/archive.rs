@@ -0,0 +1,916 @@
+//! Reading the XML entries of a DMR zip archive.
+//!
+//! Large dumps are split over several XML entries. Each entry is parsed on
+//! its own thread, with its own handle on the archive, and the records are
+//! handed back in entry order so the result is the same as a sequential
+//! read. [`ZipRecords`] reads the entries one after the other on the
+//! calling thread instead, for callers with a single reader.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Take, Write};
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::vec;
+
+use crc32fast::Hasher;
+use flate2::read::DeflateDecoder;
+use zip::result::ZipError;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::equipment::Catalogue;
+use crate::error::{Error, Result};
+use crate::model::{EquipmentType, VehicleRecord};
+use crate::parser::{ParseSummary, Recovery, XmlRecords};
+use crate::store::{append_record, read_record};
+
+/// Entries picked up when no other pattern is given
+pub const DEFAULT_ENTRY_PATTERN: &str = "*.xml";
+
+/// Records sent from a parser thread in one message
+const BATCH_SIZE: usize = 1000;
+
+/// Batches kept in memory per entry. Beyond that, an entry that is not
+/// being read yet goes to a temporary file.
+const BATCHES_IN_MEMORY: usize = 16;
+
+/// What a parser thread sends back for its entry. Records come with the
+/// equipment types they are the first in the entry to refer to.
+enum Message {
+    Records(Vec<VehicleRecord>, Vec<EquipmentType>),
+    /// The rest of the entry's records, in a temporary file
+    Spooled(File, Vec<EquipmentType>),
+    /// The entry is finished
+    Done(ParseSummary),
+}
+
+type Batch = Result<Message>;
+
+/// What a parser thread and the reader share about one entry
+#[derive(Default)]
+struct Progress {
+    /// Record batches sent and not received yet
+    queued: AtomicUsize,
+    /// The reader has reached this entry and waits for its records
+    reading: AtomicBool,
+}
+
+struct Job {
+    index: usize,
+    sender: SyncSender<Batch>,
+    progress: Arc<Progress>,
+    /// Set when the reader is dropped or failed
+    stopped: Arc<AtomicBool>,
+    in_memory: usize,
+}
+
+/// An entry waiting to be read
+struct Pending {
+    name: String,
+    size: u64,
+    receiver: Receiver<Batch>,
+    progress: Arc<Progress>,
+}
+
+/// The records of every archive entry whose name matches a pattern, in
+/// entry order and document order.
+///
+/// Entries are parsed on background threads, each to its end without
+/// waiting for the reader: records the reader is not ready for are spooled
+/// to a temporary file and replayed when their entry's turn comes, so
+/// parsing several entries takes about as long as the largest one. Only
+/// the entry being read is held back to the reader's pace. Iteration ends
+/// after the first error; dropping the iterator stops the threads.
+pub struct Records {
+    /// Entries not started yet
+    pending: vec::IntoIter<Pending>,
+    /// The entry being read
+    current: Option<Pending>,
+    batch: vec::IntoIter<VehicleRecord>,
+    /// Spooled records of the current entry, read before its next message
+    spool: Option<BufReader<File>>,
+    summary: ParseSummary,
+    catalogue: Catalogue,
+    stopped: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Records {
+    /// Start parsing the entries matching `pattern`. `open` is called once
+    /// to list the entries and then once per parser thread, to get an
+    /// independent reader.
+    pub fn open<R, F>(open: F, pattern: &str, recovery: Recovery) -> Result<Records>
+    where
+        R: Read + Seek + 'static,
+        F: Fn() -> io::Result<R> + Send + Sync + 'static,
+    {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Records::start(open, pattern, recovery, threads, BATCHES_IN_MEMORY)
+    }
+
+    fn start<R, F>(
+        open: F,
+        pattern: &str,
+        recovery: Recovery,
+        threads: usize,
+        in_memory: usize,
+    ) -> Result<Records>
+    where
+        R: Read + Seek + 'static,
+        F: Fn() -> io::Result<R> + Send + Sync + 'static,
+    {
+        let entries = matching_entries(&mut ZipArchive::new(open()?)?, pattern)?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut jobs = Vec::new();
+        let mut pending = Vec::new();
+        for (index, name, size) in entries {
+            // Room for the record batches, the spool file and the summary
+            let (sender, receiver) = sync_channel(in_memory + 2);
+            let progress = Arc::new(Progress::default());
+            jobs.push(Job {
+                index,
+                sender,
+                progress: Arc::clone(&progress),
+                stopped: Arc::clone(&stopped),
+                in_memory,
+            });
+            pending.push(Pending {
+                name,
+                size,
+                receiver,
+                progress,
+            });
+        }
+
+        let threads = threads.min(jobs.len());
+        let open = Arc::new(open);
+        let jobs = Arc::new(Mutex::new(jobs.into_iter()));
+        let workers = (0..threads)
+            .map(|_| {
+                let (open, jobs) = (Arc::clone(&open), Arc::clone(&jobs));
+                thread::spawn(move || parse_entries(&*open, recovery, &jobs))
+            })
+            .collect();
+
+        Ok(Records {
+            pending: pending.into_iter(),
+            current: None,
+            batch: Vec::new().into_iter(),
+            spool: None,
+            summary: ParseSummary::default(),
+            catalogue: Catalogue::new(),
+            stopped,
+            workers,
+        })
+    }
+
+    /// Records returned and skipped so far
+    pub fn summary(&self) -> ParseSummary {
+        self.summary
+    }
+
+    /// Equipment types described so far, including every type a returned
+    /// record refers to
+    pub fn catalogue(&self) -> &Catalogue {
+        &self.catalogue
+    }
+
+    /// Stop after an error: the remaining entries are not read
+    fn stop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.current = None;
+        self.spool = None;
+        self.pending = Vec::new().into_iter();
+    }
+
+    fn fail(&mut self, error: Error) -> Option<Result<VehicleRecord>> {
+        self.stop();
+        Some(Err(error))
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<VehicleRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.batch.next() {
+                self.summary.records += 1;
+                return Some(Ok(record));
+            }
+
+            let Some(current) = &self.current else {
+                let entry = self.pending.next()?;
+                report_start(&entry.name, entry.size);
+                entry.progress.reading.store(true, Ordering::Release);
+                self.current = Some(entry);
+                continue;
+            };
+
+            if let Some(spool) = &mut self.spool {
+                match read_record(spool) {
+                    Ok(record) => {
+                        self.summary.records += 1;
+                        return Some(Ok(record));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => self.spool = None,
+                    Err(e) => return self.fail(e.into()),
+                }
+                continue;
+            }
+
+            match current.receiver.recv() {
+                Ok(Ok(Message::Records(records, types))) => {
+                    current.progress.queued.fetch_sub(1, Ordering::AcqRel);
+                    types.into_iter().for_each(|t| self.catalogue.insert(t));
+                    self.batch = records.into_iter();
+                }
+                Ok(Ok(Message::Spooled(file, types))) => {
+                    types.into_iter().for_each(|t| self.catalogue.insert(t));
+                    self.spool = Some(BufReader::new(file));
+                }
+                Ok(Ok(Message::Done(summary))) => {
+                    report_skipped(&current.name, summary);
+                    self.summary.skipped += summary.skipped;
+                    self.current = None;
+                }
+                Ok(Err(e)) => return self.fail(e),
+                Err(_) => {
+                    let error =
+                        io::Error::other(format!("parser thread for {} stopped", current.name));
+                    return self.fail(error.into());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Records {
+    fn drop(&mut self) {
+        // Without their receivers the workers stop at their next batch
+        self.stop();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Parse every entry of the archive whose name matches `pattern` and call
+/// `on_record` for each record and the equipment types known so far,
+/// reporting progress as it goes. See [`Records`]. Returns the number of
+/// records seen and skipped across all entries.
+pub fn process_zip_file<R, F>(
+    open: F,
+    pattern: &str,
+    recovery: Recovery,
+    on_record: &mut impl FnMut(VehicleRecord, &Catalogue) -> io::Result<()>,
+) -> Result<ParseSummary>
+where
+    R: Read + Seek + 'static,
+    F: Fn() -> io::Result<R> + Send + Sync + 'static,
+{
+    let mut records = Records::open(open, pattern, recovery)?;
+    while let Some(record) = records.next() {
+        on_record(record?, records.catalogue())?;
+
+        // Progress indicator
+        let processed_count = records.summary().records;
+        if processed_count % 1000 == 0 {
+            println!("  Processed {} vehicles...", processed_count);
+        }
+    }
+
+    let summary = records.summary();
+    println!("\n✓ Successfully processed {} vehicles", summary.records);
+    if summary.skipped > 0 {
+        println!("⚠ Skipped {} damaged records", summary.skipped);
+    }
+    Ok(summary)
+}
+
+/// Worker loop: take the next entry, parse it and send its records back
+fn parse_entries<R: Read + Seek>(
+    open: &(impl Fn() -> io::Result<R> + Sync),
+    recovery: Recovery,
+    jobs: &Mutex<vec::IntoIter<Job>>,
+) {
+    let mut archive = None;
+    loop {
+        let Some(job) = jobs.lock().unwrap().next() else {
+            break;
+        };
+        let result = open_archive(&mut archive, open)
+            .and_then(|archive| parse_entry(archive, recovery, &job));
+        if let Err(e) = result {
+            // The receiver is gone if the import already stopped
+            let _ = job.sender.send(Err(e));
+        }
+    }
+}
+
+/// The worker's own handle on the archive, opened on first use
+fn open_archive<'a, R: Read + Seek>(
+    slot: &'a mut Option<ZipArchive<R>>,
+    open: &impl Fn() -> io::Result<R>,
+) -> Result<&'a mut ZipArchive<R>> {
+    if slot.is_none() {
+        *slot = Some(ZipArchive::new(open()?)?);
+    }
+    Ok(slot.as_mut().unwrap())
+}
+
+fn parse_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    recovery: Recovery,
+    job: &Job,
+) -> Result<()> {
+    let entry = archive.by_index(job.index)?;
+    let name = entry.name().to_string();
+
+    let mut handover = Handover {
+        job,
+        spool: None,
+        spooled_types: Vec::new(),
+    };
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    // Types the records sent so far refer to
+    let mut sent = Catalogue::new();
+    let mut types = Vec::new();
+    // Stream parse XML directly from zip without loading into memory
+    let mut records = XmlRecords::new(BufReader::new(entry), recovery);
+    while let Some(record) = records.next() {
+        let record = record.map_err(|e| entry_error(e, &name))?;
+        types.extend(sent.adopt(&record, records.catalogue()));
+        batch.push(record);
+        if batch.len() == BATCH_SIZE {
+            handover.send(mem::take(&mut batch), mem::take(&mut types))?;
+        }
+    }
+    let summary = records.summary();
+    if !batch.is_empty() {
+        handover.send(batch, types)?;
+    }
+    handover.finish()?;
+    // The receiver is gone if the import already stopped
+    let _ = job.sender.send(Ok(Message::Done(summary)));
+    Ok(())
+}
+
+/// Passes an entry's record batches to the reader: in memory while there
+/// is room or the reader is waiting for them, and from then on through a
+/// temporary file, so the parser never waits for the reader to reach a
+/// later entry
+struct Handover<'a> {
+    job: &'a Job,
+    spool: Option<BufWriter<File>>,
+    /// Equipment types first referred to by spooled records
+    spooled_types: Vec<EquipmentType>,
+}
+
+impl Handover<'_> {
+    fn send(&mut self, batch: Vec<VehicleRecord>, types: Vec<EquipmentType>) -> io::Result<()> {
+        let job = self.job;
+        if job.stopped.load(Ordering::Relaxed) {
+            return Err(stopped());
+        }
+        if self.spool.is_none()
+            && (job.progress.reading.load(Ordering::Acquire)
+                || job.progress.queued.load(Ordering::Acquire) < job.in_memory)
+        {
+            job.progress.queued.fetch_add(1, Ordering::AcqRel);
+            return job
+                .sender
+                .send(Ok(Message::Records(batch, types)))
+                .map_err(|_| stopped());
+        }
+
+        // Everything after the first spooled batch is spooled too, which
+        // keeps the records in order
+        let spool = match &mut self.spool {
+            Some(spool) => spool,
+            None => self.spool.insert(BufWriter::new(tempfile::tempfile()?)),
+        };
+        for record in &batch {
+            append_record(spool, record)?;
+        }
+        self.spooled_types.extend(types);
+        Ok(())
+    }
+
+    /// Hand over the spool file, if any
+    fn finish(self) -> io::Result<()> {
+        let Some(spool) = self.spool else {
+            return Ok(());
+        };
+        let mut file = spool.into_inner().map_err(|e| e.into_error())?;
+        file.flush()?;
+        file.seek(SeekFrom::Start(0))?;
+        self.job
+            .sender
+            .send(Ok(Message::Spooled(file, self.spooled_types)))
+            .map_err(|_| stopped())
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "import stopped")
+}
+
+/// Failing to read an entry means bad compressed data or CRC
+fn entry_error(error: Error, name: &str) -> Error {
+    match error {
+        Error::Io(source) => Error::Zip {
+            entry: None,
+            source: ZipError::Io(source),
+        },
+        other => other,
+    }
+    .in_entry(name)
+}
+
+/// Index, name and uncompressed size of the files matching `pattern`.
+/// Fails if one of them is compressed with a method other than stored or
+/// deflated, before anything is parsed.
+fn matching_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    pattern: &str,
+) -> Result<Vec<(usize, String, u64)>> {
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        if !glob_match(pattern, entry.name()) {
+            eprintln!("Skipping: {}", entry.name());
+            continue;
+        }
+        check_compression(entry.compression()).map_err(|e| e.in_entry(entry.name()))?;
+        entries.push((i, entry.name().to_string(), entry.size()));
+    }
+    Ok(entries)
+}
+
+/// DMR archives are stored or deflated, and only those two methods are read
+fn check_compression(method: CompressionMethod) -> Result<()> {
+    match method {
+        CompressionMethod::Stored | CompressionMethod::Deflated => Ok(()),
+        other => Err(ZipError::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "compression method {} is not supported (only Stored and Deflated)",
+                other
+            ),
+        ))
+        .into()),
+    }
+}
+
+fn report_start(name: &str, size: u64) {
+    eprintln!("Processing: {} ({:.2} KB)", name, size as f64 / 1024.0);
+}
+
+fn report_skipped(name: &str, summary: ParseSummary) {
+    if summary.skipped > 0 {
+        eprintln!(
+            "  ⚠ Skipped {} damaged records in {}",
+            summary.skipped, name
+        );
+    }
+}
+
+/// The records of every archive entry whose name matches a pattern, read
+/// one entry after the other on the calling thread.
+///
+/// Unlike [`Records`] this needs only one reader and no threads, and holds
+/// one record at a time. Iteration ends after the first error.
+pub struct ZipRecords<R> {
+    pending: vec::IntoIter<EntryLocation>,
+    state: EntryState<R>,
+    recovery: Recovery,
+    summary: ParseSummary,
+    catalogue: Catalogue,
+}
+
+/// Where an entry's data is, so it can be read from the archive's reader
+/// without keeping a borrow of the `ZipArchive`
+struct EntryLocation {
+    name: String,
+    size: u64,
+    data_start: u64,
+    compressed_size: u64,
+    compression: CompressionMethod,
+    crc32: u32,
+}
+
+enum EntryState<R> {
+    /// Between entries
+    Idle(R),
+    Reading {
+        name: String,
+        records: Box<XmlRecords<BufReader<EntryReader<R>>>>,
+    },
+    /// After an error
+    Failed,
+}
+
+impl<R: Read + Seek> ZipRecords<R> {
+    pub fn new(reader: R, pattern: &str, recovery: Recovery) -> Result<ZipRecords<R>> {
+        let mut archive = ZipArchive::new(reader)?;
+
+        let mut pending = Vec::new();
+        for (index, name, size) in matching_entries(&mut archive, pattern)? {
+            let entry = archive.by_index_raw(index)?;
+            pending.push(EntryLocation {
+                name,
+                size,
+                data_start: entry.data_start(),
+                compressed_size: entry.compressed_size(),
+                compression: entry.compression(),
+                crc32: entry.crc32(),
+            });
+        }
+
+        Ok(ZipRecords {
+            pending: pending.into_iter(),
+            state: EntryState::Idle(archive.into_inner()),
+            recovery,
+            summary: ParseSummary::default(),
+            catalogue: Catalogue::new(),
+        })
+    }
+
+    /// Records returned and skipped so far
+    pub fn summary(&self) -> ParseSummary {
+        self.summary
+    }
+
+    /// Equipment types described so far, including every type a returned
+    /// record refers to
+    pub fn catalogue(&self) -> &Catalogue {
+        &self.catalogue
+    }
+}
+
+impl<R: Read + Seek> Iterator for ZipRecords<R> {
+    type Item = Result<VehicleRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let EntryState::Reading { name, records } = &mut self.state {
+                match records.next() {
+                    Some(Ok(record)) => {
+                        self.summary.records += 1;
+                        self.catalogue.adopt(&record, records.catalogue());
+                        return Some(Ok(record));
+                    }
+                    Some(Err(e)) => {
+                        let error = entry_error(e, name);
+                        self.state = EntryState::Failed;
+                        return Some(Err(error));
+                    }
+                    None => {}
+                }
+            }
+
+            match mem::replace(&mut self.state, EntryState::Failed) {
+                EntryState::Reading { name, records } => {
+                    report_skipped(&name, records.summary());
+                    self.summary.skipped += records.summary().skipped;
+                    let reader = records.into_inner().into_inner().into_inner();
+                    self.state = EntryState::Idle(reader);
+                }
+                EntryState::Idle(reader) => {
+                    let Some(entry) = self.pending.next() else {
+                        self.state = EntryState::Idle(reader);
+                        return None;
+                    };
+                    report_start(&entry.name, entry.size);
+                    match EntryReader::open(reader, &entry) {
+                        Ok(data) => {
+                            self.state = EntryState::Reading {
+                                records: Box::new(XmlRecords::new(
+                                    BufReader::new(data),
+                                    self.recovery,
+                                )),
+                                name: entry.name,
+                            }
+                        }
+                        Err(e) => return Some(Err(entry_error(e, &entry.name))),
+                    }
+                }
+                EntryState::Failed => return None,
+            }
+        }
+    }
+}
+
+/// An entry's uncompressed data, checked against its CRC at the end
+struct EntryReader<R> {
+    data: EntryData<R>,
+    hasher: Hasher,
+    crc32: u32,
+}
+
+enum EntryData<R> {
+    Stored(Take<R>),
+    Deflated(DeflateDecoder<Take<R>>),
+}
+
+impl<R: Read + Seek> EntryReader<R> {
+    fn open(mut reader: R, entry: &EntryLocation) -> Result<EntryReader<R>> {
+        check_compression(entry.compression)?;
+        reader.seek(SeekFrom::Start(entry.data_start))?;
+        let raw = reader.take(entry.compressed_size);
+        let data = match entry.compression {
+            CompressionMethod::Deflated => EntryData::Deflated(DeflateDecoder::new(raw)),
+            _ => EntryData::Stored(raw),
+        };
+        Ok(EntryReader {
+            data,
+            hasher: Hasher::new(),
+            crc32: entry.crc32,
+        })
+    }
+
+    fn into_inner(self) -> R {
+        match self.data {
+            EntryData::Stored(raw) => raw.into_inner(),
+            EntryData::Deflated(decoder) => decoder.into_inner().into_inner(),
+        }
+    }
+}
+
+impl<R: Read> Read for EntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match &mut self.data {
+            EntryData::Stored(raw) => raw.read(buf)?,
+            EntryData::Deflated(decoder) => decoder.read(buf)?,
+        };
+        if n == 0 && !buf.is_empty() && self.hasher.clone().finalize() != self.crc32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid checksum",
+            ));
+        }
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Match an entry name against a glob with `*` (any run of characters) and
+/// `?` (one character), ignoring ASCII case
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c.eq_ignore_ascii_case(&name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{Duration, Instant};
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+    use crate::parser::DMR_NAMESPACE;
+
+    /// A zip with `entries` XML entries of `records` vehicles each, and a
+    /// file that is not an entry. Each entry has an equipment type of its
+    /// own, numbered from 100.
+    fn archive(entries: usize, records: usize) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for entry in 0..entries {
+            zip.start_file(format!("part{}.xml", entry), FileOptions::default())
+                .unwrap();
+            write!(
+                zip,
+                "<ns:ESStatistikListeModtag_I xmlns:ns=\"{}\"><ns:StatistikSamling>",
+                String::from_utf8_lossy(DMR_NAMESPACE)
+            )
+            .unwrap();
+            for i in 0..records {
+                let ident = (entry * records + i + 1) as u64;
+                write!(
+                    zip,
+                    "<ns:Statistik><ns:KoeretoejIdent>{}</ns:KoeretoejIdent>\
+                     <ns:KoeretoejArtNavn>Personbil</ns:KoeretoejArtNavn>\
+                     <ns:RegistreringNummerNummer>AB{}</ns:RegistreringNummerNummer>\
+                     <ns:KoeretoejOplysningGrundStruktur>\
+                     <ns:KoeretoejOplysningStatusDato>2021-03-24T12:47:38.000+01:00</ns:KoeretoejOplysningStatusDato>\
+                     <ns:KoeretoejOplysningFoersteRegistreringDato>2007-11-28+01:00</ns:KoeretoejOplysningFoersteRegistreringDato>\
+                     <ns:KoeretoejOplysningStelNummer>WAUZZZ4F38N069602</ns:KoeretoejOplysningStelNummer>\
+                     <ns:KoeretoejUdstyrSamlingStruktur><ns:KoeretoejUdstyrSamling>\
+                     <ns:KoeretoejUdstyrStruktur><ns:KoeretoejUdstyrAntal>1</ns:KoeretoejUdstyrAntal>\
+                     <ns:KoeretoejUdstyrTypeStruktur><ns:KoeretoejUdstyrTypeNummer>{}</ns:KoeretoejUdstyrTypeNummer>\
+                     <ns:KoeretoejUdstyrTypeNavn>Type {}</ns:KoeretoejUdstyrTypeNavn>\
+                     </ns:KoeretoejUdstyrTypeStruktur></ns:KoeretoejUdstyrStruktur>\
+                     </ns:KoeretoejUdstyrSamling></ns:KoeretoejUdstyrSamlingStruktur>\
+                     </ns:KoeretoejOplysningGrundStruktur></ns:Statistik>",
+                    ident,
+                    10000 + ident % 90000,
+                    100 + entry,
+                    entry
+                )
+                .unwrap();
+            }
+            write!(zip, "</ns:StatistikSamling></ns:ESStatistikListeModtag_I>").unwrap();
+        }
+        zip.start_file("README.txt", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"not a dump").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn opener(bytes: Vec<u8>) -> impl Fn() -> io::Result<Cursor<Vec<u8>>> + Send + Sync {
+        move || Ok(Cursor::new(bytes.clone()))
+    }
+
+    fn idents(records: impl Iterator<Item = Result<VehicleRecord>>) -> Vec<u64> {
+        records.map(|record| record.unwrap().ident).collect()
+    }
+
+    #[test]
+    fn threaded_read_matches_sequential_read() {
+        let bytes = archive(3, 2500);
+        let sequential =
+            idents(ZipRecords::new(Cursor::new(bytes.clone()), "*.xml", Recovery::Strict).unwrap());
+        assert_eq!(sequential, (1..=7500).collect::<Vec<u64>>());
+
+        for (threads, in_memory) in [(1, BATCHES_IN_MEMORY), (3, BATCHES_IN_MEMORY), (3, 0)] {
+            let mut records = Records::start(
+                opener(bytes.clone()),
+                "*.xml",
+                Recovery::Strict,
+                threads,
+                in_memory,
+            )
+            .unwrap();
+            assert_eq!(idents(&mut records), sequential, "{} threads", threads);
+            assert_eq!(records.summary().records, 7500);
+        }
+    }
+
+    #[test]
+    fn equipment_types_arrive_before_their_records() {
+        let bytes = archive(3, 2500);
+        let check = |record: VehicleRecord, catalogue: &Catalogue| {
+            let number = record.details.equipment[0].type_number.unwrap();
+            let name = catalogue.get(number).and_then(|t| t.name.clone());
+            assert_eq!(name, Some(format!("Type {}", number - 100)));
+        };
+
+        let mut records =
+            ZipRecords::new(Cursor::new(bytes.clone()), "*.xml", Recovery::Strict).unwrap();
+        while let Some(record) = records.next() {
+            check(record.unwrap(), records.catalogue());
+        }
+        assert_eq!(records.catalogue().len(), 3);
+
+        for (threads, in_memory) in [(1, BATCHES_IN_MEMORY), (3, 0)] {
+            let mut records = Records::start(
+                opener(bytes.clone()),
+                "*.xml",
+                Recovery::Strict,
+                threads,
+                in_memory,
+            )
+            .unwrap();
+            while let Some(record) = records.next() {
+                check(record.unwrap(), records.catalogue());
+            }
+            assert_eq!(records.catalogue().len(), 3);
+        }
+    }
+
+    #[test]
+    fn pattern_selects_entries() {
+        let bytes = archive(3, 10);
+        let records = Records::open(opener(bytes.clone()), "PART1.*", Recovery::Strict).unwrap();
+        assert_eq!(idents(records), (11..=20).collect::<Vec<u64>>());
+        let records = ZipRecords::new(Cursor::new(bytes), "*.txt", Recovery::Strict).unwrap();
+        assert!(idents(records).is_empty());
+    }
+
+    #[test]
+    fn later_entries_do_not_wait_for_the_reader() {
+        let bytes = archive(3, 5 * BATCH_SIZE);
+        let mut records = Records::start(opener(bytes), "*.xml", Recovery::Strict, 3, 1).unwrap();
+        assert_eq!(records.next().unwrap().unwrap().ident, 1);
+
+        // The first entry is held back to the reader's pace, the other two
+        // are parsed to the end
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while records.workers.iter().filter(|w| w.is_finished()).count() < 2 {
+            assert!(Instant::now() < deadline, "parser threads are waiting");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(records.count(), 15 * BATCH_SIZE - 1);
+    }
+
+    #[test]
+    fn dropping_the_reader_stops_the_threads() {
+        let bytes = archive(3, 5 * BATCH_SIZE);
+        let mut records = Records::start(opener(bytes), "*.xml", Recovery::Strict, 3, 1).unwrap();
+        records.next().unwrap().unwrap();
+        drop(records);
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn threaded_read_is_faster() {
+        let entries = 4;
+        let bytes = archive(entries, 50_000);
+
+        let start = Instant::now();
+        let sequential = ZipRecords::new(Cursor::new(bytes.clone()), "*.xml", Recovery::Strict)
+            .unwrap()
+            .count();
+        let sequential_time = start.elapsed();
+
+        let start = Instant::now();
+        let threaded = Records::open(opener(bytes), "*.xml", Recovery::Strict)
+            .unwrap()
+            .count();
+        let threaded_time = start.elapsed();
+
+        assert_eq!(sequential, threaded);
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        println!(
+            "{} entries on {} CPUs: sequential {:?}, threaded {:?}",
+            entries, cpus, sequential_time, threaded_time
+        );
+        if cpus >= entries {
+            assert!(threaded_time * 2 < sequential_time);
+        }
+    }
+
+    #[test]
+    fn unsupported_compression_is_rejected_up_front() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("part0.xml", FileOptions::default()).unwrap();
+        zip.write_all(b"<dump/>").unwrap();
+        let bzip2 = FileOptions::default().compression_method(CompressionMethod::Bzip2);
+        zip.start_file("part1.xml", bzip2).unwrap();
+        zip.write_all(b"<dump/>").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let message = "zip entry part1.xml: compression method Bzip2 is not supported \
+                       (only Stored and Deflated)";
+        let error = Records::open(opener(bytes.clone()), "*.xml", Recovery::Skip)
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::Zip { .. }));
+        assert_eq!(error.to_string(), message);
+        let error = ZipRecords::new(Cursor::new(bytes.clone()), "*.xml", Recovery::Skip)
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::Zip { .. }));
+        assert_eq!(error.to_string(), message);
+
+        // Entries left out by the pattern are not checked
+        let records = ZipRecords::new(Cursor::new(bytes), "part0.xml", Recovery::Skip).unwrap();
+        assert_eq!(records.count(), 0);
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*.xml", "part0.XML"));
+        assert!(glob_match("part?.xml", "part1.xml"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("*.xml", "part0.xml.bak"));
+        assert!(!glob_match("part?.xml", "part10.xml"));
+    }
+}
@@ -0,0 +1,181 @@
+//! The directory of downloaded archives and its retention policy.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::archive::glob_match;
+use crate::ftp::parse_name_timestamp;
+
+/// Directory below the store used when `cache.directory` is not set
+pub const DEFAULT_CACHE_DIR: &str = "downloads";
+
+/// Suffix of archives that are still being downloaded
+pub const PARTIAL_SUFFIX: &str = ".part";
+
+/// Whether `path` already holds a download of `size` bytes. An unknown
+/// size (0) never matches.
+pub fn is_downloaded(path: &Path, size: u64) -> bool {
+    size > 0 && fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.len() == size)
+}
+
+/// Delete all archives matching `pattern` but the newest remote archive
+/// `current` and the `keep` newest others, and any partial download that
+/// is not for `current`. Returns the deleted files.
+pub fn prune(dir: &Path, pattern: &str, keep: usize, current: &str) -> io::Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    let mut removed = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        if let Some(archive) = name.strip_suffix(PARTIAL_SUFFIX) {
+            if archive != current && glob_match(pattern, archive) {
+                fs::remove_file(entry.path())?;
+                removed.push(entry.path());
+            }
+        } else if name != current && glob_match(pattern, &name) {
+            let modified = entry
+                .metadata()?
+                .modified()
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            archives.push((parse_name_timestamp(&name), modified, entry.path()));
+        }
+    }
+
+    // Newest first, by the stamp in the name and then the file time
+    archives.sort_by(|a, b| b.cmp(a));
+    for (_, _, path) in archives.into_iter().skip(keep) {
+        fs::remove_file(&path)?;
+        removed.push(path);
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    const PATTERN: &str = "ESStatistikListeModtag-*.zip";
+
+    fn archive(day: u32) -> String {
+        format!("ESStatistikListeModtag-202610{:02}-120000.zip", day)
+    }
+
+    /// Create `names` in a new directory, the first one modified last
+    fn cache(names: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        for (i, name) in names.iter().enumerate() {
+            let file = File::create(dir.path().join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(60 * i as u64))
+                .unwrap();
+        }
+        dir
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn keeps_the_newest_by_stamp() {
+        // Modification times run the other way
+        let names = [archive(1), archive(2), archive(3), archive(4)];
+        let dir = cache(&names.each_ref().map(String::as_str));
+
+        let removed = prune(dir.path(), PATTERN, 2, &archive(4)).unwrap();
+        assert_eq!(removed, [dir.path().join(archive(1))]);
+        assert_eq!(files(dir.path()), [archive(2), archive(3), archive(4)]);
+
+        assert!(prune(dir.path(), PATTERN, 2, &archive(4))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn keep_zero_leaves_only_the_current_archive() {
+        let names = [archive(1), archive(2), archive(3)];
+        let dir = cache(&names.each_ref().map(String::as_str));
+
+        prune(dir.path(), PATTERN, 0, &archive(3)).unwrap();
+        assert_eq!(files(dir.path()), [archive(3)]);
+    }
+
+    #[test]
+    fn never_deletes_the_current_archive() {
+        // The server went back to an older dump
+        let names = [archive(1), archive(2), archive(3)];
+        let dir = cache(&names.each_ref().map(String::as_str));
+
+        prune(dir.path(), PATTERN, 0, &archive(1)).unwrap();
+        assert_eq!(files(dir.path()), [archive(1)]);
+
+        let dir = cache(&names.each_ref().map(String::as_str));
+        prune(dir.path(), PATTERN, 1, &archive(1)).unwrap();
+        assert_eq!(files(dir.path()), [archive(1), archive(3)]);
+    }
+
+    #[test]
+    fn leaves_other_files_alone() {
+        let current = archive(5);
+        let stale_part = format!("{}{}", archive(4), PARTIAL_SUFFIX);
+        let current_part = format!("{}{}", current, PARTIAL_SUFFIX);
+        let dir = cache(&[
+            &archive(3),
+            &stale_part,
+            &current_part,
+            "notes.txt",
+            "other-20261001-120000.zip",
+            "other.zip.part",
+        ]);
+        fs::create_dir(dir.path().join(archive(1))).unwrap();
+
+        let removed = prune(dir.path(), PATTERN, 0, &current).unwrap();
+        assert_eq!(
+            removed,
+            [dir.path().join(&stale_part), dir.path().join(archive(3))]
+        );
+        assert_eq!(
+            files(dir.path()),
+            [
+                archive(1),
+                current_part,
+                "notes.txt".to_string(),
+                "other-20261001-120000.zip".to_string(),
+                "other.zip.part".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn downloaded_when_the_size_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(archive(1));
+        assert!(!is_downloaded(&path, 10));
+
+        fs::write(&path, b"0123456789").unwrap();
+        assert!(is_downloaded(&path, 10));
+        assert!(!is_downloaded(&path, 11));
+        assert!(!is_downloaded(&path, 9));
+
+        let empty = dir.path().join(archive(2));
+        fs::write(&empty, b"").unwrap();
+        assert!(!is_downloaded(&empty, 0));
+        let dir_len = fs::metadata(dir.path()).unwrap().len();
+        assert!(!is_downloaded(dir.path(), dir_len));
+    }
+}
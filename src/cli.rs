@@ -0,0 +1,677 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use autoplate::archive::DEFAULT_ENTRY_PATTERN;
+use autoplate::config::{flag_name, KEYS};
+use autoplate::dates::parse_date;
+use autoplate::equipment::EquipmentQuery;
+use autoplate::export::Format;
+use autoplate::fuel::Drivetrain;
+use autoplate::model::{Status, VehicleRecord};
+use autoplate::parser::Recovery;
+use autoplate::{plate, vin};
+
+/// What the user asked for on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Load an archive (local or downloaded) into the store
+    Import {
+        file: Option<String>,
+        incremental: bool,
+        /// Glob selecting the zip entries to parse
+        entries: String,
+        recovery: Recovery,
+        config: ConfigArgs,
+    },
+    /// Download the newest archive into the cache without importing it
+    Fetch { config: ConfigArgs },
+    /// Print the full record for a plate or VIN, from an archive or the store
+    Lookup {
+        key: LookupKey,
+        file: Option<String>,
+        recovery: Recovery,
+        filter: Filter,
+        config: ConfigArgs,
+    },
+    /// Write every vehicle, from an archive or the store, to a file
+    Export {
+        format: Format,
+        /// Comma-separated column names, all columns when not given
+        columns: Option<String>,
+        /// Standard output when not given
+        output: Option<String>,
+        file: Option<String>,
+        entries: String,
+        recovery: Recovery,
+        filter: Filter,
+        config: ConfigArgs,
+    },
+    /// Report on vehicle equipment, from an archive or the store
+    Equipment {
+        view: EquipmentView,
+        file: Option<String>,
+        entries: String,
+        recovery: Recovery,
+        filter: Filter,
+        config: ConfigArgs,
+    },
+    /// Count the vehicles with each drivetrain
+    Drivetrains {
+        file: Option<String>,
+        entries: String,
+        recovery: Recovery,
+        filter: Filter,
+        config: ConfigArgs,
+    },
+    /// List the vehicles past their periodic inspection deadline
+    Overdue {
+        /// Today when not given
+        as_of: Option<NaiveDate>,
+        file: Option<String>,
+        entries: String,
+        recovery: Recovery,
+        filter: Filter,
+        config: ConfigArgs,
+    },
+}
+
+/// Which vehicles a lookup, export or report covers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Any of these, or every drivetrain when empty
+    pub drivetrains: Vec<Drivetrain>,
+    /// Registration statuses (KoeretoejRegistreringStatus), any of these or
+    /// every status when empty. `None` when `--status` is not given,
+    /// leaving the choice to the command and the `view.hide-inactive`
+    /// setting.
+    pub statuses: Option<Vec<Status>>,
+    /// Statuses of the vehicle itself (KoeretoejOplysningStatus), any of
+    /// these or every status when empty. An exported car has the
+    /// registration status "Afmeldt" and the vehicle status "Eksporteret".
+    pub vehicle_statuses: Vec<Status>,
+}
+
+impl Filter {
+    /// Whether the vehicle passes every part of the filter
+    pub fn matches(&self, record: &VehicleRecord) -> bool {
+        let drivetrain = self.drivetrains.is_empty()
+            || record
+                .details
+                .motor
+                .drivetrain
+                .is_some_and(|drivetrain| self.drivetrains.contains(&drivetrain));
+        let status = match &self.statuses {
+            Some(statuses) if !statuses.is_empty() => record
+                .registration_status
+                .as_ref()
+                .is_some_and(|status| statuses.contains(status)),
+            _ => true,
+        };
+        let vehicle_status = self.vehicle_statuses.is_empty()
+            || record
+                .details
+                .status
+                .as_ref()
+                .is_some_and(|status| self.vehicle_statuses.contains(status));
+        drivetrain && status && vehicle_status
+    }
+}
+
+/// What `autoplate equipment` reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EquipmentView {
+    /// Every equipment type with the number of vehicles that have it
+    Catalogue,
+    /// The vehicles fitted with the equipment
+    With(EquipmentQuery),
+    /// Vehicles by how many of the equipment they have
+    Distribution(EquipmentQuery),
+}
+
+/// Config file and settings given on the command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigArgs {
+    pub file: Option<String>,
+    /// `(key, value)` pairs that override the file and the environment
+    pub overrides: Vec<(String, String)>,
+}
+
+/// What a lookup searches for, normalised to the form used in the dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupKey {
+    Plate(String),
+    Vin(String),
+}
+
+impl LookupKey {
+    /// Whether the vehicle has the plate or VIN looked for
+    pub fn matches(&self, record: &VehicleRecord) -> bool {
+        match self {
+            LookupKey::Plate(plate) => record.plate.as_deref() == Some(plate.as_str()),
+            LookupKey::Vin(vin) => record.details.vin.as_deref() == Some(vin.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for LookupKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupKey::Plate(plate) => write!(f, "plate {}", plate),
+            LookupKey::Vin(vin) => write!(f, "VIN {}", vin),
+        }
+    }
+}
+
+pub const USAGE: &str = "\
+Usage:
+  autoplate [import] [--incremental] [--entries=GLOB] [--strict] [FILE]
+                                              Import FILE, or the newest archive on the FTP server,
+                                              parsing only entries matching GLOB (default *.xml).
+                                              --incremental applies only the differences and
+                                              needs every entry, so it takes no --entries
+  autoplate fetch                             Download the newest archive into the cache
+  autoplate lookup <PLATE> [FILE]             Show the vehicle with PLATE from FILE or the store
+  autoplate lookup --vin <VIN> [FILE]         Show the vehicle with stelnummer VIN
+  autoplate export [--format=csv|json|ndjson|parquet] [--columns=NAME,...] [--output=OUT]
+                   [--entries=GLOB] [--strict] [FILE]
+                                              Write every vehicle in FILE or the store to OUT
+                                              (default standard output); --columns picks
+                                              and orders the CSV or Parquet columns
+  autoplate equipment [--with=TYPE | --distribution=TYPE] [--entries=GLOB] [--strict] [FILE]
+                                              List the equipment types in FILE or the store,
+                                              the vehicles fitted with TYPE, or how many
+                                              vehicles have each number of TYPE. TYPE is a
+                                              type number or part of its name, e.g.
+                                              anhængertræk or airbag
+  autoplate drivetrains [--entries=GLOB] [--strict] [FILE]
+                                              Count the vehicles in FILE or the store by
+                                              drivetrain
+  autoplate overdue [--as-of=YYYY-MM-DD] [--entries=GLOB] [--strict] [FILE]
+                                              List the vehicles in FILE or the store whose
+                                              periodic inspection was due before the given
+                                              day (default today)
+
+export and equipment take --drivetrain=LIST to include only vehicles with one
+of the comma-separated drivetrains: petrol, diesel, electric, plug-in-hybrid,
+hybrid, gas, hydrogen or other.
+
+lookup, export, equipment, drivetrains and overdue take --status=LIST to
+include only vehicles whose registration (KoeretoejRegistreringStatus) has
+one of the comma-separated statuses: registered, deregistered, exported or
+scrapped (or the Danish registreret, afmeldt, eksporteret, skrotet), or all.
+Without it every status is included, unless the view.hide-inactive setting
+is true: then only registered vehicles are. overdue lists only registered
+vehicles without --status.
+
+--vehicle-status=LIST takes the same statuses and filters on the status of
+the vehicle itself (KoeretoejOplysningStatus) instead: an exported car is
+deregistered, and exported as a vehicle.
+
+Damaged records are skipped with a warning, and parsing resumes at the next
+ns:Statistik. With --strict the first damaged record or invalid field value
+stops the command instead.
+
+FTP, cache and view options (also read from autoplate.conf or --config=FILE, and
+from AUTOPLATE_FTP_HOST style environment variables):
+  --ftp-host=HOST --ftp-port=PORT --ftp-user=USER --ftp-password=PASSWORD
+  --ftp-directory=DIR --ftp-pattern=GLOB --ftp-mode=passive|active
+  --ftp-tls=explicit|implicit|off (FTPS, the server certificate is verified)
+  --ftp-connect-timeout=SECS --ftp-read-timeout=SECS --ftp-retries=N
+  --ftp-retry-delay=SECS (doubled after each failed attempt)
+  --cache-directory=DIR --cache-keep=N (older archives kept, default 2)
+  --view-hide-inactive (list only registered vehicles when --status is not given)
+
+Exit codes:
+  1 not found, 2 usage, 3 config, 4 FTP (transient, retry later),
+  5 FTP (permanent), 6 I/O, 7 zip, 8 XML syntax, 9 XML structure, 10 field value";
+
+/// Parse the arguments after the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = match args.first().map(String::as_str) {
+        Some("import") => ("import", &args[1..]),
+        Some("fetch") => ("fetch", &args[1..]),
+        Some("lookup") => ("lookup", &args[1..]),
+        Some("export") => ("export", &args[1..]),
+        Some("equipment") => ("equipment", &args[1..]),
+        Some("drivetrains") => ("drivetrains", &args[1..]),
+        Some("overdue") => ("overdue", &args[1..]),
+        _ => ("import", args),
+    };
+
+    let mut flags = Vec::new();
+    let mut positional = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            // `--format csv` is the same as `--format=csv`
+            let value = rest
+                .next()
+                .ok_or_else(|| format!("{} needs a value\n\n{}", arg, USAGE))?;
+            flags.push(format!("{}={}", arg, value));
+        } else if arg.starts_with("--") {
+            flags.push(arg.clone());
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+    let flags = flags.iter().map(String::as_str);
+
+    let unexpected = |what: &str| Err(format!("unexpected {}\n\n{}", what, USAGE));
+    let mut positional = positional.into_iter();
+
+    match command {
+        "fetch" => {
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                if !config_flag(flag, &mut config) {
+                    return unexpected(&format!("option {}", flag));
+                }
+            }
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Fetch { config })
+        }
+        "lookup" => {
+            let mut by_vin = false;
+            let mut recovery = Recovery::Skip;
+            let mut filter = Filter::default();
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+                match name {
+                    "--vin" => by_vin = true,
+                    "--status" => filter.statuses = Some(statuses(value)?),
+                    "--vehicle-status" => filter.vehicle_statuses = statuses(value)?,
+                    "--strict" => recovery = Recovery::Strict,
+                    _ if config_flag(flag, &mut config) => {}
+                    _ => return unexpected(&format!("option {}", flag)),
+                }
+            }
+            let value = positional
+                .next()
+                .ok_or_else(|| format!("lookup needs a plate or VIN\n\n{}", USAGE))?;
+            let key = if by_vin {
+                LookupKey::Vin(vin::normalize(&value))
+            } else {
+                LookupKey::Plate(plate::normalize(&value))
+            };
+            let file = positional.next();
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Lookup {
+                key,
+                file,
+                recovery,
+                filter,
+                config,
+            })
+        }
+        "export" => {
+            let mut format = Format::Csv;
+            let mut columns = None;
+            let mut output = None;
+            let mut entries = DEFAULT_ENTRY_PATTERN.to_string();
+            let mut recovery = Recovery::Skip;
+            let mut filter = Filter::default();
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+                match name {
+                    "--format" => format = value.parse()?,
+                    "--drivetrain" => filter.drivetrains = drivetrains(value)?,
+                    "--status" => filter.statuses = Some(statuses(value)?),
+                    "--vehicle-status" => filter.vehicle_statuses = statuses(value)?,
+                    "--columns" => columns = Some(value.to_string()),
+                    "--output" => output = Some(value.to_string()),
+                    "--entries" => entries = value.to_string(),
+                    "--strict" => recovery = Recovery::Strict,
+                    _ if config_flag(flag, &mut config) => {}
+                    _ => return unexpected(&format!("option {}", flag)),
+                }
+            }
+            let file = positional.next();
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Export {
+                format,
+                columns,
+                output,
+                file,
+                entries,
+                recovery,
+                filter,
+                config,
+            })
+        }
+        "equipment" => {
+            let mut view = EquipmentView::Catalogue;
+            let mut entries = DEFAULT_ENTRY_PATTERN.to_string();
+            let mut recovery = Recovery::Skip;
+            let mut filter = Filter::default();
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+                match name {
+                    "--with" | "--distribution" => {
+                        if value.trim().is_empty() {
+                            return Err(format!("{} needs a value\n\n{}", name, USAGE));
+                        }
+                        if view != EquipmentView::Catalogue {
+                            return Err(format!(
+                                "--with and --distribution cannot be combined\n\n{}",
+                                USAGE
+                            ));
+                        }
+                        let query = EquipmentQuery::parse(value);
+                        view = if name == "--with" {
+                            EquipmentView::With(query)
+                        } else {
+                            EquipmentView::Distribution(query)
+                        };
+                    }
+                    "--drivetrain" => filter.drivetrains = drivetrains(value)?,
+                    "--status" => filter.statuses = Some(statuses(value)?),
+                    "--vehicle-status" => filter.vehicle_statuses = statuses(value)?,
+                    "--entries" => entries = value.to_string(),
+                    "--strict" => recovery = Recovery::Strict,
+                    _ if config_flag(flag, &mut config) => {}
+                    _ => return unexpected(&format!("option {}", flag)),
+                }
+            }
+            let file = positional.next();
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Equipment {
+                view,
+                file,
+                entries,
+                recovery,
+                filter,
+                config,
+            })
+        }
+        "drivetrains" => {
+            let mut entries = DEFAULT_ENTRY_PATTERN.to_string();
+            let mut recovery = Recovery::Skip;
+            let mut filter = Filter::default();
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+                match name {
+                    "--status" => filter.statuses = Some(statuses(value)?),
+                    "--vehicle-status" => filter.vehicle_statuses = statuses(value)?,
+                    "--entries" => entries = value.to_string(),
+                    "--strict" => recovery = Recovery::Strict,
+                    _ if config_flag(flag, &mut config) => {}
+                    _ => return unexpected(&format!("option {}", flag)),
+                }
+            }
+            let file = positional.next();
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Drivetrains {
+                file,
+                entries,
+                recovery,
+                filter,
+                config,
+            })
+        }
+        "overdue" => {
+            let mut as_of = None;
+            let mut entries = DEFAULT_ENTRY_PATTERN.to_string();
+            let mut recovery = Recovery::Skip;
+            let mut filter = Filter::default();
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+                match name {
+                    "--as-of" => {
+                        as_of = Some(parse_date(value).map_err(|e| format!("--as-of: {}", e))?)
+                    }
+                    "--status" => filter.statuses = Some(statuses(value)?),
+                    "--vehicle-status" => filter.vehicle_statuses = statuses(value)?,
+                    "--entries" => entries = value.to_string(),
+                    "--strict" => recovery = Recovery::Strict,
+                    _ if config_flag(flag, &mut config) => {}
+                    _ => return unexpected(&format!("option {}", flag)),
+                }
+            }
+            let file = positional.next();
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Overdue {
+                as_of,
+                file,
+                entries,
+                recovery,
+                filter,
+                config,
+            })
+        }
+        _ => {
+            let mut incremental = false;
+            let mut entries = DEFAULT_ENTRY_PATTERN.to_string();
+            let mut recovery = Recovery::Skip;
+            let mut config = ConfigArgs::default();
+            for flag in flags {
+                match flag {
+                    "--incremental" => incremental = true,
+                    "--strict" => recovery = Recovery::Strict,
+                    other => match other.strip_prefix("--entries=") {
+                        Some(glob) => entries = glob.to_string(),
+                        None if config_flag(other, &mut config) => {}
+                        None => return unexpected(&format!("option {}", other)),
+                    },
+                }
+            }
+            // Vehicles in the entries left out would count as removed
+            if incremental && entries != DEFAULT_ENTRY_PATTERN {
+                return Err(format!(
+                    "--incremental reads the whole archive and cannot be combined with \
+                     --entries\n\n{}",
+                    USAGE
+                ));
+            }
+            let file = positional.next();
+            if let Some(extra) = positional.next() {
+                return unexpected(&format!("argument {}", extra));
+            }
+            Ok(Command::Import {
+                file,
+                incremental,
+                entries,
+                recovery,
+                config,
+            })
+        }
+    }
+}
+
+/// Options whose value may also be given as the next argument
+const VALUE_FLAGS: &[&str] = &[
+    "--format",
+    "--columns",
+    "--output",
+    "--entries",
+    "--with",
+    "--distribution",
+    "--drivetrain",
+    "--status",
+    "--vehicle-status",
+    "--as-of",
+    "--config",
+];
+
+/// A comma-separated list of drivetrains
+fn drivetrains(value: &str) -> Result<Vec<Drivetrain>, String> {
+    value
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// A comma-separated list of statuses, by their English or DMR name.
+/// "all" selects every status.
+fn statuses(value: &str) -> Result<Vec<Status>, String> {
+    let mut statuses = Vec::new();
+    for name in value.split(',').filter(|name| !name.trim().is_empty()) {
+        let status = match name.trim().to_lowercase().as_str() {
+            "all" => return Ok(Vec::new()),
+            "registered" | "registreret" => Status::Registered,
+            "deregistered" | "afmeldt" => Status::Deregistered,
+            "exported" | "eksporteret" => Status::Exported,
+            "scrapped" | "skrotet" => Status::Scrapped,
+            other => {
+                return Err(format!(
+                    "unknown status {:?}, expected one of: registered, deregistered, \
+                     exported, scrapped, all",
+                    other
+                ))
+            }
+        };
+        statuses.push(status);
+    }
+    Ok(statuses)
+}
+
+/// Take `--config=FILE` or a `--ftp-host=...` style setting, returning
+/// false for anything else. A setting without a value means "true".
+fn config_flag(flag: &str, config: &mut ConfigArgs) -> bool {
+    if let Some(file) = flag.strip_prefix("--config=") {
+        config.file = Some(file.to_string());
+        return true;
+    }
+    let (name, value) = flag.split_once('=').unwrap_or((flag, "true"));
+    match KEYS.iter().find(|key| flag_name(key) == name) {
+        Some(key) => {
+            config.overrides.push((key.to_string(), value.to_string()));
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// A car with the given registration and vehicle status
+    fn vehicle(registration: Option<Status>, vehicle: Option<Status>) -> VehicleRecord {
+        let mut record = VehicleRecord {
+            registration_status: registration,
+            ..VehicleRecord::default()
+        };
+        record.details.status = vehicle;
+        record
+    }
+
+    #[test]
+    fn status_names() {
+        assert_eq!(
+            statuses("registered,Afmeldt, EKSPORTERET,skrotet").unwrap(),
+            [
+                Status::Registered,
+                Status::Deregistered,
+                Status::Exported,
+                Status::Scrapped
+            ]
+        );
+        assert_eq!(
+            statuses("registreret,deregistered,exported,scrapped").unwrap(),
+            [
+                Status::Registered,
+                Status::Deregistered,
+                Status::Exported,
+                Status::Scrapped
+            ]
+        );
+        // Every status
+        assert_eq!(statuses("all").unwrap(), []);
+        assert_eq!(statuses("registered,all").unwrap(), []);
+
+        let error = statuses("registered,stolen").unwrap_err();
+        assert!(error.contains("\"stolen\""), "{}", error);
+    }
+
+    #[test]
+    fn status_flags_filter_different_fields() {
+        // An exported car: deregistered, and exported as a vehicle
+        let exported = vehicle(Some(Status::Deregistered), Some(Status::Exported));
+        let registered = vehicle(Some(Status::Registered), Some(Status::Registered));
+        let filter = |status: Option<&str>, vehicle_status: &str| Filter {
+            statuses: status.map(|s| statuses(s).unwrap()),
+            vehicle_statuses: statuses(vehicle_status).unwrap(),
+            ..Filter::default()
+        };
+
+        let everything = filter(None, "all");
+        assert!(everything.matches(&exported) && everything.matches(&registered));
+        assert!(filter(Some("all"), "").matches(&exported));
+
+        let by_registration = filter(Some("exported"), "all");
+        assert!(!by_registration.matches(&exported));
+        let by_registration = filter(Some("deregistered"), "all");
+        assert!(by_registration.matches(&exported));
+        assert!(!by_registration.matches(&registered));
+
+        let by_vehicle = filter(None, "exported");
+        assert!(by_vehicle.matches(&exported));
+        assert!(!by_vehicle.matches(&registered));
+        assert!(!filter(None, "deregistered").matches(&exported));
+
+        // Both have to match
+        assert!(filter(Some("deregistered"), "exported").matches(&exported));
+        assert!(!filter(Some("registered"), "exported").matches(&exported));
+
+        // A vehicle without the status asked for is left out
+        assert!(!filter(Some("registered"), "").matches(&vehicle(None, None)));
+        assert!(!filter(None, "registered").matches(&vehicle(None, None)));
+    }
+
+    #[test]
+    fn config_file_flag() {
+        for flags in [&["--config", "my.conf"][..], &["--config=my.conf"]] {
+            let args = args(&[&["fetch"], flags, &["--ftp-port=990"]].concat());
+            let Ok(Command::Fetch { config }) = parse_args(&args) else {
+                panic!("expected a fetch command");
+            };
+            assert_eq!(config.file.as_deref(), Some("my.conf"));
+        }
+        assert!(parse_args(&args(&["fetch", "--config"])).is_err());
+    }
+
+    #[test]
+    fn status_flags() {
+        let Ok(Command::Overdue { filter, .. }) = parse_args(&args(&[
+            "overdue",
+            "--status=all",
+            "--vehicle-status",
+            "exported,scrapped",
+        ])) else {
+            panic!("expected an overdue command");
+        };
+        assert_eq!(filter.statuses, Some(Vec::new()));
+        assert_eq!(
+            filter.vehicle_statuses,
+            [Status::Exported, Status::Scrapped]
+        );
+
+        let Ok(Command::Drivetrains { filter, .. }) = parse_args(&args(&["drivetrains"])) else {
+            panic!("expected a drivetrains command");
+        };
+        assert_eq!(filter, Filter::default());
+    }
+}
@@ -0,0 +1,480 @@
+//! Settings for where and how archives are fetched and kept, and how
+//! vehicles are listed.
+//!
+//! Every setting has a key such as `ftp.host`. Values are taken from, in
+//! increasing order of precedence, the built-in defaults, the config file,
+//! `AUTOPLATE_FTP_HOST` style environment variables and `--ftp-host=...`
+//! style command line flags.
+//!
+//! The config file is a list of `key = value` lines, optionally grouped
+//! under `[section]` headers that prefix the keys:
+//!
+//! ```text
+//! [ftp]
+//! host = ftp.example.dk
+//! mode = active
+//! ```
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Config file read from the working directory when no other is named
+pub const DEFAULT_CONFIG_FILE: &str = "autoplate.conf";
+
+/// Every setting, by the key used in the config file
+pub const KEYS: &[&str] = &[
+    "ftp.host",
+    "ftp.port",
+    "ftp.user",
+    "ftp.password",
+    "ftp.directory",
+    "ftp.pattern",
+    "ftp.mode",
+    "ftp.tls",
+    "ftp.connect-timeout",
+    "ftp.read-timeout",
+    "ftp.retries",
+    "ftp.retry-delay",
+    "cache.directory",
+    "cache.keep",
+    "view.hide-inactive",
+];
+
+/// How the data connection is set up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FtpMode {
+    Passive,
+    Active,
+}
+
+impl fmt::Display for FtpMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FtpMode::Passive => f.write_str("passive"),
+            FtpMode::Active => f.write_str("active"),
+        }
+    }
+}
+
+/// Whether and how the connection is encrypted (FTPS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FtpTls {
+    /// Plain FTP
+    Off,
+    /// Connect in plain text and switch to TLS with AUTH TLS (RFC 4217)
+    Explicit,
+    /// Speak TLS from the start, usually on port 990
+    Implicit,
+}
+
+impl fmt::Display for FtpTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FtpTls::Off => f.write_str("off"),
+            FtpTls::Explicit => f.write_str("explicit"),
+            FtpTls::Implicit => f.write_str("implicit"),
+        }
+    }
+}
+
+/// The FTP server publishing the DMR dumps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub directory: String,
+    /// Glob selecting the archives in `directory`
+    pub pattern: String,
+    pub mode: FtpMode,
+    pub tls: FtpTls,
+    pub connect_timeout: Duration,
+    /// Longest wait for data on the control or data connection
+    pub read_timeout: Duration,
+    /// Further attempts after a transient failure
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    pub retry_delay: Duration,
+}
+
+impl Default for FtpConfig {
+    fn default() -> Self {
+        FtpConfig {
+            host: "5.44.137.84".to_string(),
+            port: 21,
+            user: "anonymous".to_string(),
+            password: "anonymous".to_string(),
+            directory: "/ESStatistikListeModtag".to_string(),
+            pattern: "ESStatistikListeModtag-*.zip".to_string(),
+            mode: FtpMode::Passive,
+            tls: FtpTls::Off,
+            connect_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(120),
+            retries: 5,
+            retry_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Where downloaded archives are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Defaults to a directory inside the store
+    pub directory: Option<String>,
+    /// Archives kept besides the one just fetched
+    pub keep: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            directory: None,
+            keep: 2,
+        }
+    }
+}
+
+/// How lookups, exports and reports list vehicles
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ViewConfig {
+    /// Leave out deregistered, exported and scrapped vehicles unless a
+    /// status is asked for
+    pub hide_inactive: bool,
+}
+
+/// All settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub ftp: FtpConfig,
+    pub cache: CacheConfig,
+    pub view: ViewConfig,
+}
+
+impl Config {
+    /// Build the settings from the config file, the environment and the
+    /// `(key, value)` pairs given on the command line.
+    ///
+    /// `file` must exist when given; otherwise `AUTOPLATE_CONFIG` or
+    /// `autoplate.conf` is read if present.
+    pub fn load(file: Option<&str>, overrides: &[(String, String)]) -> Result<Config, String> {
+        Config::load_with(file, |name| env::var(name).ok(), overrides)
+    }
+
+    /// [`Config::load`] with the environment variables looked up by `env`
+    fn load_with(
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+        overrides: &[(String, String)],
+    ) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        // A named file must exist, the default one is optional
+        let (path, required) = match file {
+            Some(path) => (path.to_string(), true),
+            None => match env("AUTOPLATE_CONFIG") {
+                Some(path) => (path, true),
+                None => (DEFAULT_CONFIG_FILE.to_string(), false),
+            },
+        };
+        if required || Path::new(&path).exists() {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| format!("cannot read config file {}: {}", path, e))?;
+            config
+                .read_file(&text)
+                .map_err(|e| format!("{}:{}", path, e))?;
+        }
+
+        for key in KEYS {
+            if let Some(value) = env(&env_name(key)) {
+                config
+                    .set(key, &value)
+                    .map_err(|e| format!("{}: {}", env_name(key), e))?;
+            }
+        }
+
+        for (key, value) in overrides {
+            config
+                .set(key, value)
+                .map_err(|e| format!("{}: {}", flag_name(key), e))?;
+        }
+
+        Ok(config)
+    }
+
+    fn read_file(&mut self, text: &str) -> Result<(), String> {
+        let mut section = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = format!("{}.", name.trim());
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{}: expected key = value", i + 1))?;
+            let key = format!("{}{}", section, key.trim());
+            self.set(&key, value.trim())
+                .map_err(|e| format!("{}: {}: {}", i + 1, key, e))?;
+        }
+        Ok(())
+    }
+
+    /// Set one setting from its text form
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let ftp = &mut self.ftp;
+        match key {
+            "ftp.host" => ftp.host = value.to_string(),
+            "ftp.port" => {
+                ftp.port = value
+                    .parse()
+                    .map_err(|_| format!("invalid port {:?}", value))?
+            }
+            "ftp.user" => ftp.user = value.to_string(),
+            "ftp.password" => ftp.password = value.to_string(),
+            "ftp.directory" => ftp.directory = value.to_string(),
+            "ftp.pattern" => ftp.pattern = value.to_string(),
+            "ftp.mode" => {
+                ftp.mode = match value.to_ascii_lowercase().as_str() {
+                    "passive" => FtpMode::Passive,
+                    "active" => FtpMode::Active,
+                    _ => return Err(format!("expected passive or active, got {:?}", value)),
+                }
+            }
+            "ftp.tls" => {
+                ftp.tls = match value.to_ascii_lowercase().as_str() {
+                    "explicit" => FtpTls::Explicit,
+                    "implicit" => FtpTls::Implicit,
+                    "off" => FtpTls::Off,
+                    _ => {
+                        return Err(format!(
+                            "expected explicit, implicit or off, got {:?}",
+                            value
+                        ))
+                    }
+                }
+            }
+            "ftp.connect-timeout" => ftp.connect_timeout = seconds(value)?,
+            "ftp.read-timeout" => ftp.read_timeout = seconds(value)?,
+            "ftp.retries" => {
+                ftp.retries = value
+                    .parse()
+                    .map_err(|_| format!("expected a number of retries, got {:?}", value))?
+            }
+            "ftp.retry-delay" => ftp.retry_delay = seconds(value)?,
+            "cache.directory" => self.cache.directory = Some(value.to_string()),
+            "cache.keep" => {
+                self.cache.keep = value
+                    .parse()
+                    .map_err(|_| format!("expected a number of archives, got {:?}", value))?
+            }
+            "view.hide-inactive" => self.view.hide_inactive = boolean(value)?,
+            _ => return Err("unknown setting".to_string()),
+        }
+        Ok(())
+    }
+}
+
+/// `ftp.host` is read from `AUTOPLATE_FTP_HOST`
+pub fn env_name(key: &str) -> String {
+    format!(
+        "AUTOPLATE_{}",
+        key.replace(['.', '-'], "_").to_ascii_uppercase()
+    )
+}
+
+/// `ftp.host` is set with `--ftp-host=...`
+pub fn flag_name(key: &str) -> String {
+    format!("--{}", key.replace('.', "-"))
+}
+
+fn seconds(value: &str) -> Result<Duration, String> {
+    match value.parse() {
+        Ok(0) | Err(_) => Err(format!(
+            "expected a positive number of seconds, got {:?}",
+            value
+        )),
+        Ok(secs) => Ok(Duration::from_secs(secs)),
+    }
+}
+
+fn boolean(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("expected true or false, got {:?}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn tls_setting() {
+        let mut config = Config::default();
+        assert_eq!(config.ftp.tls, FtpTls::Off);
+        for (value, tls) in [
+            ("explicit", FtpTls::Explicit),
+            ("Implicit", FtpTls::Implicit),
+            ("off", FtpTls::Off),
+        ] {
+            config.set("ftp.tls", value).unwrap();
+            assert_eq!(config.ftp.tls, tls);
+        }
+        assert!(config.set("ftp.tls", "true").is_err());
+
+        config.read_file("[ftp]\ntls = implicit\n").unwrap();
+        assert_eq!(config.ftp.tls, FtpTls::Implicit);
+    }
+
+    /// Load with `text` as the config file and `env` as the environment
+    fn load(
+        text: &str,
+        env: &[(&str, &str)],
+        overrides: &[(&str, &str)],
+    ) -> Result<Config, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("autoplate.conf");
+        fs::write(&path, text).unwrap();
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let overrides: Vec<(String, String)> = overrides
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Config::load_with(
+            Some(path.to_str().unwrap()),
+            |name| env.get(name).cloned(),
+            &overrides,
+        )
+        .map_err(|e| e.replace(path.to_str().unwrap(), "autoplate.conf"))
+    }
+
+    #[test]
+    fn sections_prefix_keys() {
+        let config = load(
+            "# DMR mirror\n\
+             view.hide-inactive = yes\n\
+             \n\
+             [ftp]\n\
+             host = ftp.example.dk\n\
+             \x20 mode = Active  \n\
+             retry-delay=10\n\
+             [ cache ]\n\
+             keep = 0\n",
+            &[],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(config.ftp.host, "ftp.example.dk");
+        assert_eq!(config.ftp.mode, FtpMode::Active);
+        assert_eq!(config.ftp.retry_delay, Duration::from_secs(10));
+        assert_eq!(config.ftp.port, 21);
+        assert_eq!(config.cache.keep, 0);
+        assert!(config.view.hide_inactive);
+    }
+
+    #[test]
+    fn file_errors_name_the_line() {
+        let error = |text: &str| load(text, &[], &[]).unwrap_err();
+        assert_eq!(
+            error("[ftp]\nhost = a\nbogus = 1\n"),
+            "autoplate.conf:3: ftp.bogus: unknown setting"
+        );
+        assert_eq!(
+            error("[ftp]\nport 21\n"),
+            "autoplate.conf:2: expected key = value"
+        );
+        // Keys outside their section are unknown
+        assert_eq!(
+            error("[cache]\nhost = a\n"),
+            "autoplate.conf:2: cache.host: unknown setting"
+        );
+        assert_eq!(
+            error("[cache]\nkeep = some\n"),
+            "autoplate.conf:2: cache.keep: expected a number of archives, got \"some\""
+        );
+    }
+
+    #[test]
+    fn file_then_environment_then_flags() {
+        let file = "[ftp]\nhost = file.example\nport = 2121\nmode = active\nretries = 1\n";
+        let env = [
+            ("AUTOPLATE_FTP_HOST", "env.example"),
+            ("AUTOPLATE_FTP_PORT", "990"),
+            ("AUTOPLATE_FTP_READ_TIMEOUT", "7"),
+            ("AUTOPLATE_CACHE_KEEP", "4"),
+        ];
+        let flags = [("ftp.host", "flag.example"), ("cache.keep", "1")];
+
+        let config = load(file, &[], &[]).unwrap();
+        assert_eq!(
+            (config.ftp.host.as_str(), config.ftp.port),
+            ("file.example", 2121)
+        );
+
+        let config = load(file, &env, &[]).unwrap();
+        assert_eq!(
+            (config.ftp.host.as_str(), config.ftp.port),
+            ("env.example", 990)
+        );
+        assert_eq!(config.cache.keep, 4);
+
+        let config = load(file, &env, &flags).unwrap();
+        assert_eq!(config.ftp.host, "flag.example");
+        assert_eq!(config.ftp.port, 990);
+        assert_eq!(config.ftp.mode, FtpMode::Active);
+        assert_eq!(config.ftp.retries, 1);
+        assert_eq!(config.ftp.read_timeout, Duration::from_secs(7));
+        assert_eq!(config.cache.keep, 1);
+        assert_eq!(config.ftp.user, FtpConfig::default().user);
+    }
+
+    #[test]
+    fn environment_and_flag_errors_name_their_source() {
+        let error = load("", &[("AUTOPLATE_FTP_PORT", "ftp")], &[]).unwrap_err();
+        assert_eq!(error, "AUTOPLATE_FTP_PORT: invalid port \"ftp\"");
+        let error = load("", &[], &[("ftp.retries", "-1")]).unwrap_err();
+        assert_eq!(
+            error,
+            "--ftp-retries: expected a number of retries, got \"-1\""
+        );
+    }
+
+    #[test]
+    fn config_file_from_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("other.conf");
+        fs::write(&path, "ftp.host = other.example\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let env = |name: &str| (name == "AUTOPLATE_CONFIG").then(|| path.clone());
+
+        let config = Config::load_with(None, env, &[]).unwrap();
+        assert_eq!(config.ftp.host, "other.example");
+
+        // A file named in the environment or on the command line must exist
+        let missing = dir.path().join("missing.conf");
+        let missing = missing.to_str().unwrap().to_string();
+        let env = |name: &str| (name == "AUTOPLATE_CONFIG").then(|| missing.clone());
+        let error = Config::load_with(None, env, &[]).unwrap_err();
+        assert!(error.starts_with("cannot read config file"), "{}", error);
+        assert!(Config::load_with(Some(&missing), |_| None, &[]).is_err());
+    }
+
+    #[test]
+    fn names_for_keys() {
+        assert_eq!(env_name("ftp.read-timeout"), "AUTOPLATE_FTP_READ_TIMEOUT");
+        assert_eq!(flag_name("ftp.read-timeout"), "--ftp-read-timeout");
+        assert_eq!(flag_name("view.hide-inactive"), "--view-hide-inactive");
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
+
+/// Why a DMR date or timestamp could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateError {
+    Empty,
+    InvalidDate(String),
+    InvalidTime(String),
+    InvalidOffset(String),
+    MissingTime,
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateError::Empty => write!(f, "empty date"),
+            DateError::InvalidDate(s) => write!(f, "{:?} is not a valid YYYY-MM-DD date", s),
+            DateError::InvalidTime(s) => write!(f, "{:?} is not a valid HH:MM:SS time", s),
+            DateError::InvalidOffset(s) => {
+                write!(
+                    f,
+                    "{:?} is not a valid timezone offset (expected Z or +HH:MM)",
+                    s
+                )
+            }
+            DateError::MissingTime => write!(f, "timestamp has no T separated time part"),
+        }
+    }
+}
+
+impl std::error::Error for DateError {}
+
+/// Parse an `xs:date` value such as `2020-12-23+01:00`.
+///
+/// DMR writes dates with the offset of the Danish zone at that day. The
+/// offset does not move the calendar date, so it is validated and dropped.
+pub fn parse_date(text: &str) -> Result<NaiveDate, DateError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(DateError::Empty);
+    }
+    let (date, offset) = split_date(text)?;
+    parse_offset(offset)?;
+    Ok(date)
+}
+
+/// Parse an `xs:dateTime` value such as `2021-03-24T12:47:38.000+01:00`.
+///
+/// Fractional seconds are optional and may have any precision. A timestamp
+/// without an offset is taken to be UTC.
+pub fn parse_datetime(text: &str) -> Result<DateTime<FixedOffset>, DateError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(DateError::Empty);
+    }
+    let (date, rest) = split_date(text)?;
+    let rest = rest.strip_prefix('T').ok_or(DateError::MissingTime)?;
+
+    let offset_start = rest.find(['Z', '+', '-']).unwrap_or(rest.len());
+    let (time, offset) = rest.split_at(offset_start);
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f")
+        .map_err(|_| DateError::InvalidTime(time.to_string()))?;
+    let offset = parse_offset(offset)?;
+
+    date.and_time(time)
+        .and_local_timezone(offset)
+        .single()
+        .ok_or_else(|| DateError::InvalidOffset(offset.to_string()))
+}
+
+/// Split the leading `YYYY-MM-DD` off `text`
+fn split_date(text: &str) -> Result<(NaiveDate, &str), DateError> {
+    let invalid = || DateError::InvalidDate(text.to_string());
+    let (date, rest) = match (text.get(..10), text.get(10..)) {
+        (Some(date), Some(rest)) => (date, rest),
+        _ => return Err(invalid()),
+    };
+    let bytes = date.as_bytes();
+    let well_formed = bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit());
+    if !well_formed {
+        return Err(invalid());
+    }
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())?;
+    Ok((date, rest))
+}
+
+/// Parse an XML Schema timezone: empty, `Z` or `(+|-)HH:MM` up to 14:00
+fn parse_offset(text: &str) -> Result<FixedOffset, DateError> {
+    let invalid = || DateError::InvalidOffset(text.to_string());
+    let sign = match text.as_bytes().first() {
+        None | Some(b'Z') if text.len() <= 1 => return Ok(FixedOffset::east_opt(0).unwrap()),
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes) = text[1..].split_once(':').ok_or_else(invalid)?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return Err(invalid());
+    }
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if minutes >= 60 || hours * 60 + minutes > 14 * 60 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(seconds: i32) -> FixedOffset {
+        FixedOffset::east_opt(seconds).unwrap()
+    }
+
+    #[test]
+    fn date_drops_offset() {
+        let date = NaiveDate::from_ymd_opt(2020, 12, 23).unwrap();
+        assert_eq!(parse_date("2020-12-23+01:00"), Ok(date));
+        assert_eq!(parse_date("2020-12-23Z"), Ok(date));
+        assert_eq!(parse_date(" 2020-12-23 "), Ok(date));
+        assert_eq!(parse_date("2020-12-23-05:30"), Ok(date));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(parse_date(""), Err(DateError::Empty));
+        assert_eq!(
+            parse_date("2020-02-30"),
+            Err(DateError::InvalidDate("2020-02-30".to_string()))
+        );
+        assert_eq!(
+            parse_date("20-12-2020"),
+            Err(DateError::InvalidDate("20-12-2020".to_string()))
+        );
+        assert_eq!(
+            parse_date("2020-1-230"),
+            Err(DateError::InvalidDate("2020-1-230".to_string()))
+        );
+        assert_eq!(
+            parse_date("2020-12-23+1:00"),
+            Err(DateError::InvalidOffset("+1:00".to_string()))
+        );
+        assert_eq!(
+            parse_date("2020-12-23+14:30"),
+            Err(DateError::InvalidOffset("+14:30".to_string()))
+        );
+    }
+
+    #[test]
+    fn datetime_keeps_offset() {
+        let parsed = parse_datetime("2021-03-24T12:47:38.000+01:00").unwrap();
+        assert_eq!(parsed.offset(), &offset(3600));
+        assert_eq!(parsed.to_rfc3339(), "2021-03-24T12:47:38+01:00");
+
+        let parsed = parse_datetime("2021-03-24T12:47:38.123456-02:30").unwrap();
+        assert_eq!(parsed.offset(), &offset(-9000));
+        assert_eq!(parsed.timestamp_subsec_micros(), 123456);
+    }
+
+    #[test]
+    fn datetime_without_offset_is_utc() {
+        let parsed = parse_datetime("2021-03-24T12:47:38").unwrap();
+        assert_eq!(parsed.offset(), &offset(0));
+        assert_eq!(parsed, parse_datetime("2021-03-24T12:47:38Z").unwrap());
+    }
+
+    #[test]
+    fn invalid_datetimes() {
+        assert_eq!(parse_datetime("  "), Err(DateError::Empty));
+        assert_eq!(parse_datetime("2021-03-24"), Err(DateError::MissingTime));
+        assert_eq!(
+            parse_datetime("2021-03-24 12:47:38"),
+            Err(DateError::MissingTime)
+        );
+        assert_eq!(
+            parse_datetime("2021-03-24T25:00:00+01:00"),
+            Err(DateError::InvalidTime("25:00:00".to_string()))
+        );
+        assert_eq!(
+            parse_datetime("2021-03-24T12:47:38+0100"),
+            Err(DateError::InvalidOffset("+0100".to_string()))
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt::Display;
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use autoplate::equipment::{Catalogue, Distribution, TypeUsage};
+use autoplate::fuel::Drivetrain;
+use autoplate::model::{Coded, Designation, EquipmentType, VehicleRecord};
+use autoplate::quality::check_record;
+use autoplate::vin;
+
+/// Print every known field of a vehicle record, skipping empty ones.
+/// Equipment names come from `catalogue`.
+pub fn print_vehicle(record: &VehicleRecord, catalogue: &Catalogue) {
+    let details = &record.details;
+    let designation = &details.designation;
+    let motor = &details.motor;
+
+    println!(
+        "\n=== {} (KoeretoejIdent {}) ===",
+        record.plate.as_deref().unwrap_or("(no plate)"),
+        record.ident
+    );
+
+    field("Kind", name(&record.kind));
+    field("Usage", name(&record.usage));
+    field("Plate class", record.plate_class);
+    field("Plate expiry", record.plate_expiry);
+    field(
+        "Registration status",
+        with_date(
+            &record.registration_status,
+            &record.registration_status_date,
+        ),
+    );
+    field(
+        "Vehicle status",
+        with_date(&details.status, &details.status_date),
+    );
+    field("First registration", details.first_registration);
+    field("Created from", details.created_from.as_ref());
+
+    field(
+        "Make / model",
+        Some(make_model(designation)).filter(|s| !s.is_empty()),
+    );
+    field("Type", name(&designation.vehicle_type));
+    field("Model year", details.model_year);
+    field("Body type", name(&details.body_type));
+    field("Colour", name(&details.colour));
+    field("Condition", details.condition.as_ref());
+    field(
+        "VIN",
+        details.vin.as_ref().map(|vin| match &details.vin_location {
+            Some(location) => format!("{} ({})", vin, location),
+            None => vin.clone(),
+        }),
+    );
+    field(
+        "VIN manufacturer",
+        details.vin.as_deref().and_then(vin::manufacturer),
+    );
+    field("Type approval", details.type_approval_number.as_ref());
+    field(
+        "Type notification",
+        details.type_notification_number.as_ref(),
+    );
+
+    field("Total weight (kg)", details.total_weight);
+    field(
+        "Technical total weight (kg)",
+        details.technical_total_weight,
+    );
+    field(
+        "Curb weight (kg)",
+        range(details.curb_weight_min, details.curb_weight_max),
+    );
+    field("Train weight (kg)", details.train_weight);
+    field("Axles", details.axles);
+    field("Driving axles", details.driving_axles);
+    field("Seats", range(details.seats_min, details.seats_max));
+    field("Doors", details.doors);
+    field("Towing possible", details.coupling_possible);
+    field("Towing unbraked (kg)", details.coupling_weight_unbraked);
+    field("Towing braked (kg)", details.coupling_weight_braked);
+    field("NCAP tested", details.ncap_test);
+    field("Suitable for taxi", details.taxi_suitable);
+    field("Traffic damage", details.traffic_damage);
+    field("Norm", name(&details.norm));
+    field("Particle filter", details.environment.particle_filter);
+    field("CO2 (g/km)", details.environment.co2_emission);
+
+    field("Cylinders", motor.cylinders);
+    field("Displacement (cm3)", motor.displacement);
+    field("Max power (kW)", motor.max_power);
+    field("Odometer (km)", motor.odometer);
+    field("Innovative technology", motor.innovative_technology);
+    field("Comment", details.comment.as_ref());
+
+    field("Drivetrain", motor.drivetrain);
+    if !motor.fuels.is_empty() {
+        println!("Fuels:");
+        for fuel in &motor.fuels {
+            let mut line = fuel.drive_type.name.clone().unwrap_or_default();
+            if fuel.primary == Some(true) {
+                line.push_str(" (primary)");
+            }
+            if let Some(km_per_liter) = fuel.km_per_liter {
+                line.push_str(&format!(", {} km/l", km_per_liter));
+            }
+            if let Some(consumption) = fuel.electric_consumption {
+                line.push_str(&format!(", {} Wh/km", consumption));
+            }
+            println!("  - {}", line);
+        }
+    }
+
+    if !details.equipment.is_empty() {
+        println!("Equipment:");
+        for equipment in &details.equipment {
+            let name = equipment
+                .type_number
+                .and_then(|number| catalogue.get(number))
+                .map_or("?", equipment_name);
+            println!("  - {} x {}", equipment.fitted(), name);
+        }
+    }
+
+    if let Some(inspection) = &record.inspection {
+        println!("Latest inspection:");
+        field("  Type", inspection.kind.as_ref());
+        field("  Date", inspection.date);
+        field("  Result", inspection.result.as_ref());
+        field(
+            "  Status",
+            with_date(&inspection.status, &inspection.status_date),
+        );
+    }
+
+    let findings = check_record(record);
+    if !findings.is_empty() {
+        println!("Data quality:");
+        for finding in &findings {
+            println!("  - {} {}", finding.element, finding.message);
+        }
+    }
+}
+
+/// One line for a vehicle in a list: plate, KoeretoejIdent, make and
+/// model, and how many of the equipment asked about it has
+pub fn print_vehicle_line(record: &VehicleRecord, fitted: u32) {
+    println!(
+        "{:<8} {:>17}  {}  ({} fitted)",
+        record.plate.as_deref().unwrap_or("-"),
+        record.ident,
+        make_model(&record.details.designation),
+        fitted
+    );
+}
+
+/// Every equipment type in `usage` with the number of vehicles that have
+/// it, described from `catalogue`
+pub fn print_catalogue(catalogue: &Catalogue, usage: &TypeUsage) {
+    println!("\n=== Equipment types ({} total) ===", usage.len());
+    println!("{:>6}  {:>8}  Name", "Number", "Vehicles");
+    for (number, vehicles) in usage.counts() {
+        let unknown = EquipmentType::default();
+        let equipment_type = catalogue.get(number).unwrap_or(&unknown);
+        let shown = [
+            (equipment_type.shown_at_inspection, "at inspection"),
+            (equipment_type.shown_on_inquiry, "on inquiry"),
+            (
+                equipment_type.shown_on_standard_creation,
+                "standard creation",
+            ),
+        ]
+        .into_iter()
+        .filter(|(shown, _)| *shown == Some(true))
+        .map(|(_, place)| place)
+        .collect::<Vec<_>>();
+        let mut line = format!(
+            "{:>6}  {:>8}  {}",
+            number,
+            vehicles,
+            equipment_name(equipment_type)
+        );
+        if !shown.is_empty() {
+            line.push_str(&format!(" (shown {})", shown.join(", ")));
+        }
+        println!("{}", line);
+    }
+}
+
+/// Vehicles by number of items fitted, for the equipment `types`
+pub fn print_distribution(distribution: &Distribution, types: &[&EquipmentType]) {
+    let names = types
+        .iter()
+        .map(|equipment_type| {
+            format!(
+                "{} ({})",
+                equipment_name(equipment_type),
+                equipment_type.number.unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    println!("\n=== {} ===", names.join(", "));
+    println!("{:>6}  {:>8}", "Fitted", "Vehicles");
+    let total = distribution.total().max(1) as f64;
+    for (fitted, vehicles) in distribution.counts() {
+        println!(
+            "{:>6}  {:>8}  {:>5.1}%",
+            fitted,
+            vehicles,
+            vehicles as f64 * 100.0 / total
+        );
+    }
+}
+
+/// One line of the overdue inspection report
+pub fn overdue_line(record: &VehicleRecord, due: NaiveDate, today: NaiveDate) -> String {
+    let last = match &record.inspection {
+        Some(inspection) => format!(
+            "{} {}",
+            inspection
+                .date
+                .map_or_else(|| "?".to_string(), |date| date.to_string()),
+            inspection.result.as_deref().unwrap_or("")
+        ),
+        None => "never".to_string(),
+    };
+    format!(
+        "{:<8} {:>17}  {:<12} {:<28} {:<22} {}  {:>5}",
+        record.plate.as_deref().unwrap_or("-"),
+        record.ident,
+        name(&record.kind).unwrap_or("?"),
+        make_model(&record.details.designation),
+        last.trim_end(),
+        due,
+        (today - due).num_days()
+    )
+}
+
+/// Header of the overdue inspection report, aligned with [`overdue_line`]
+pub fn print_overdue_header() {
+    println!(
+        "{:<8} {:>17}  {:<12} {:<28} {:<22} {:<10}  {:>5}",
+        "Plate", "Ident", "Kind", "Make / model", "Last inspection", "Due", "Days"
+    );
+}
+
+/// Vehicles by drivetrain; `None` counts those without any fuel listed
+pub fn print_drivetrains(counts: &BTreeMap<Option<Drivetrain>, usize>) {
+    let total: usize = counts.values().sum();
+    println!("\n=== Drivetrains ({} vehicles) ===", total);
+    for (drivetrain, vehicles) in counts {
+        let label = drivetrain.map_or("unknown", Drivetrain::as_str);
+        println!(
+            "{:<16} {:>8}  {:>5.1}%",
+            label,
+            vehicles,
+            *vehicles as f64 * 100.0 / total.max(1) as f64
+        );
+    }
+}
+
+fn field(label: &str, value: Option<impl Display>) {
+    if let Some(value) = value {
+        println!("{:<28} {}", format!("{}:", label), value);
+    }
+}
+
+fn name(coded: &Option<Coded>) -> Option<&str> {
+    coded.as_ref().and_then(|c| c.name.as_deref())
+}
+
+fn make_model(designation: &Designation) -> String {
+    [&designation.make, &designation.model, &designation.variant]
+        .into_iter()
+        .filter_map(name)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn equipment_name(equipment_type: &EquipmentType) -> &str {
+    equipment_type.name.as_deref().unwrap_or("?")
+}
+
+fn with_date<S: Display, D: Display>(status: &Option<S>, date: &Option<D>) -> Option<String> {
+    status.as_ref().map(|status| match date {
+        Some(date) => format!("{} ({})", status, date),
+        None => status.to_string(),
+    })
+}
+
+fn range(min: Option<u32>, max: Option<u32>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) if min != max => Some(format!("{}-{}", min, max)),
+        (Some(value), _) | (None, Some(value)) => Some(value.to_string()),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,232 @@
+//! The equipment-type catalogue and queries over vehicle equipment lists.
+//!
+//! Every KoeretoejUdstyrStruktur in the dump repeats the full description
+//! of its type. Records keep only the type number, and [`Catalogue`] holds
+//! each type once, keyed by KoeretoejUdstyrTypeNummer. [`EquipmentQuery`]
+//! picks types by number or name for questions such as "which vehicles
+//! have a towbar".
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::model::{EquipmentType, VehicleRecord};
+
+/// Every equipment type seen, by KoeretoejUdstyrTypeNummer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Catalogue {
+    types: BTreeMap<u32, EquipmentType>,
+}
+
+impl Catalogue {
+    pub fn new() -> Self {
+        Catalogue::default()
+    }
+
+    /// Add a type unless its number is known already, so the first
+    /// description seen is kept. Names are trimmed; types without a
+    /// number are ignored.
+    pub fn insert(&mut self, equipment_type: EquipmentType) {
+        if let Some(number) = equipment_type.number {
+            if let Entry::Vacant(entry) = self.types.entry(number) {
+                entry.insert(normalize(equipment_type));
+            }
+        }
+    }
+
+    /// Copy the types `record` refers to from `source`, unless they are
+    /// known already. Returns the types added.
+    pub fn adopt(&mut self, record: &VehicleRecord, source: &Catalogue) -> Vec<EquipmentType> {
+        let mut added = Vec::new();
+        for number in record
+            .details
+            .equipment
+            .iter()
+            .filter_map(|e| e.type_number)
+        {
+            if let (Entry::Vacant(entry), Some(equipment_type)) =
+                (self.types.entry(number), source.get(number))
+            {
+                added.push(entry.insert(equipment_type.clone()).clone());
+            }
+        }
+        added
+    }
+
+    /// Take every description in `newer`, replacing the known ones
+    pub fn update(&mut self, newer: &Catalogue) {
+        self.types
+            .extend(newer.types.iter().map(|(&n, t)| (n, t.clone())));
+    }
+
+    /// All types, by number
+    pub fn types(&self) -> impl Iterator<Item = &EquipmentType> {
+        self.types.values()
+    }
+
+    pub fn get(&self, number: u32) -> Option<&EquipmentType> {
+        self.types.get(&number)
+    }
+
+    /// The types selected by `query`
+    pub fn find<'a>(
+        &'a self,
+        query: &'a EquipmentQuery,
+    ) -> impl Iterator<Item = &'a EquipmentType> + 'a {
+        self.types()
+            .filter(|equipment_type| query.matches(equipment_type))
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+fn normalize(equipment_type: EquipmentType) -> EquipmentType {
+    EquipmentType {
+        name: equipment_type
+            .name
+            .as_deref()
+            .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" ")),
+        ..equipment_type
+    }
+}
+
+/// How many vehicles are fitted with each equipment type. Types a vehicle
+/// lists with a count of 0 are included, with no vehicles.
+#[derive(Debug, Clone, Default)]
+pub struct TypeUsage {
+    vehicles: BTreeMap<u32, usize>,
+}
+
+impl TypeUsage {
+    pub fn new() -> Self {
+        TypeUsage::default()
+    }
+
+    pub fn add(&mut self, record: &VehicleRecord) {
+        let mut counted = Vec::new();
+        for equipment in &record.details.equipment {
+            let Some(number) = equipment.type_number else {
+                continue;
+            };
+            let vehicles = self.vehicles.entry(number).or_default();
+            if equipment.fitted() > 0 && !counted.contains(&number) {
+                *vehicles += 1;
+                counted.push(number);
+            }
+        }
+    }
+
+    /// `(type number, vehicles fitted)` pairs, by type number
+    pub fn counts(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+        self.vehicles
+            .iter()
+            .map(|(&number, &vehicles)| (number, vehicles))
+    }
+
+    /// Number of types seen
+    pub fn len(&self) -> usize {
+        self.vehicles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vehicles.is_empty()
+    }
+}
+
+/// Selects equipment types by KoeretoejUdstyrTypeNummer, or by a part of
+/// their name ignoring case, e.g. "9901" or "airbag"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EquipmentQuery {
+    Number(u32),
+    Name(String),
+}
+
+impl EquipmentQuery {
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        match text.parse() {
+            Ok(number) => EquipmentQuery::Number(number),
+            Err(_) => EquipmentQuery::Name(text.to_lowercase()),
+        }
+    }
+
+    pub fn matches(&self, equipment_type: &EquipmentType) -> bool {
+        match self {
+            EquipmentQuery::Number(number) => equipment_type.number == Some(*number),
+            EquipmentQuery::Name(name) => equipment_type
+                .name
+                .as_ref()
+                .is_some_and(|n| n.to_lowercase().contains(name.as_str())),
+        }
+    }
+
+    /// Number of matching items fitted to a vehicle, 0 when it has none.
+    /// Names are looked up in `catalogue`.
+    pub fn count(&self, record: &VehicleRecord, catalogue: &Catalogue) -> u32 {
+        record
+            .details
+            .equipment
+            .iter()
+            .filter(|equipment| {
+                equipment.type_number.is_some_and(|number| match self {
+                    EquipmentQuery::Number(wanted) => number == *wanted,
+                    EquipmentQuery::Name(_) => catalogue
+                        .get(number)
+                        .is_some_and(|equipment_type| self.matches(equipment_type)),
+                })
+            })
+            .map(|equipment| equipment.fitted())
+            .sum()
+    }
+}
+
+impl fmt::Display for EquipmentQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquipmentQuery::Number(number) => write!(f, "equipment type {}", number),
+            EquipmentQuery::Name(name) => write!(f, "equipment {:?}", name),
+        }
+    }
+}
+
+/// How many vehicles have each number of the equipment selected by a
+/// query. Vehicles without it are counted under 0.
+#[derive(Debug, Clone)]
+pub struct Distribution {
+    query: EquipmentQuery,
+    counts: BTreeMap<u32, usize>,
+}
+
+impl Distribution {
+    pub fn new(query: EquipmentQuery) -> Self {
+        Distribution {
+            query,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, record: &VehicleRecord, catalogue: &Catalogue) {
+        *self
+            .counts
+            .entry(self.query.count(record, catalogue))
+            .or_default() += 1;
+    }
+
+    /// `(items fitted, vehicles)` pairs, by number of items
+    pub fn counts(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+        self.counts
+            .iter()
+            .map(|(&fitted, &vehicles)| (fitted, vehicles))
+    }
+
+    /// Vehicles seen
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
@@ -0,0 +1,180 @@
+//! The error type shared by every part of autoplate.
+//!
+//! Each kind of failure maps to its own process exit code, so schedulers
+//! can tell a network outage they should retry from a broken dump that
+//! needs a human.
+
+use std::fmt;
+use std::io;
+
+use zip::result::ZipError;
+
+use crate::parser::FieldError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Bad command line
+    Usage(String),
+    /// Bad setting in the config file, environment or flags
+    Config(String),
+    /// A lookup found nothing
+    NotFound(String),
+    /// The FTP server could not be reached or refused the download.
+    /// `transient` failures may succeed on a later run.
+    Ftp {
+        transient: bool,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    Io(io::Error),
+    /// The archive, or one of its entries, cannot be read
+    Zip {
+        entry: Option<String>,
+        source: ZipError,
+    },
+    /// The XML is not well-formed
+    Xml {
+        entry: Option<String>,
+        position: usize,
+        source: quick_xml::Error,
+    },
+    /// Well-formed XML that does not have the shape of a DMR dump
+    Schema {
+        entry: Option<String>,
+        position: usize,
+        message: String,
+    },
+    /// A field whose text does not fit its type
+    Validation {
+        entry: Option<String>,
+        position: usize,
+        ident: u64,
+        source: FieldError,
+    },
+}
+
+impl Error {
+    /// Process exit code reported for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NotFound(_) => 1,
+            Error::Usage(_) => 2,
+            Error::Config(_) => 3,
+            Error::Ftp {
+                transient: true, ..
+            } => 4,
+            Error::Ftp {
+                transient: false, ..
+            } => 5,
+            Error::Io(_) => 6,
+            Error::Zip { .. } => 7,
+            Error::Xml { .. } => 8,
+            Error::Schema { .. } => 9,
+            Error::Validation { .. } => 10,
+        }
+    }
+
+    /// Record which zip entry the error happened in
+    pub fn in_entry(mut self, name: &str) -> Self {
+        match &mut self {
+            Error::Zip { entry, .. }
+            | Error::Xml { entry, .. }
+            | Error::Schema { entry, .. }
+            | Error::Validation { entry, .. } => {
+                entry.get_or_insert_with(|| name.to_string());
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // "in part1.xml " when the entry is known
+        let place = |entry: &Option<String>| match entry {
+            Some(entry) => format!("in {} ", entry),
+            None => String::new(),
+        };
+
+        match self {
+            Error::Usage(message) | Error::Config(message) | Error::NotFound(message) => {
+                f.write_str(message)
+            }
+            Error::Ftp { source, .. } => write!(f, "FTP: {}", source),
+            Error::Io(source) => write!(f, "{}", source),
+            Error::Zip {
+                entry: None,
+                source,
+            } => write!(f, "zip: {}", source),
+            Error::Zip {
+                entry: Some(entry),
+                source,
+            } => write!(f, "zip entry {}: {}", entry, source),
+            Error::Xml {
+                entry,
+                position,
+                source,
+            } => write!(
+                f,
+                "XML syntax error {}at byte {}: {}",
+                place(entry),
+                position,
+                source
+            ),
+            Error::Schema {
+                entry,
+                position,
+                message,
+            } => write!(
+                f,
+                "unexpected structure {}at byte {}: {}",
+                place(entry),
+                position,
+                message
+            ),
+            Error::Validation {
+                entry,
+                position,
+                ident,
+                source,
+            } => write!(
+                f,
+                "KoeretoejIdent {} {}at byte {}: {}",
+                ident,
+                place(entry),
+                position,
+                source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Ftp { source, .. } => Some(source.as_ref()),
+            Error::Io(source) => Some(source),
+            Error::Zip { source, .. } => Some(source),
+            Error::Xml { source, .. } => Some(source),
+            Error::Validation { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(error: ZipError) -> Self {
+        Error::Zip {
+            entry: None,
+            source: error,
+        }
+    }
+}
@@ -0,0 +1,494 @@
+//! Writing vehicle records for spreadsheets and other tools.
+//!
+//! Tabular formats flatten a record into the columns of [`COLUMNS`]; JSON
+//! keeps the nesting of the model. Column and field names are part of the
+//! output format: new ones may be added, but existing ones keep their name
+//! and meaning.
+//!
+//! CSV and Parquet are the tabular formats.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::equipment::Catalogue;
+use crate::inspection;
+use crate::model::{Coded, Decimal, Fuel, VehicleRecord};
+
+mod csv;
+mod json;
+mod parquet;
+
+pub use self::csv::CsvWriter;
+pub use self::json::JsonWriter;
+pub use self::parquet::{ParquetWriter, DICTIONARY_LIMIT, ROW_GROUP_ROWS};
+
+/// Output formats of `autoplate export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    /// One pretty-printed array
+    Json,
+    /// One compact JSON object per line
+    Ndjson,
+    Parquet,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            "parquet" => Ok(Format::Parquet),
+            _ => Err(format!(
+                "unknown export format {:?}, expected csv, json, ndjson or parquet",
+                s
+            )),
+        }
+    }
+}
+
+/// Where exported records go, in one of the formats
+pub trait RecordWriter {
+    /// Write a record; `catalogue` describes the equipment types it
+    /// refers to
+    fn write(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()>;
+
+    /// Complete the output and flush it
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// One cell of a flattened record
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(u64),
+    Decimal(Decimal),
+    Bool(bool),
+    Date(NaiveDate),
+    DateTime(DateTime<FixedOffset>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => f.write_str(text),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            Value::DateTime(time) => f.write_str(&time.to_rfc3339()),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Value::Integer(n.into())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Value::Integer(n)
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(d: Decimal) -> Self {
+        Value::Decimal(d)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<NaiveDate> for Value {
+    fn from(date: NaiveDate) -> Self {
+        Value::Date(date)
+    }
+}
+
+impl From<DateTime<FixedOffset>> for Value {
+    fn from(time: DateTime<FixedOffset>) -> Self {
+        Value::DateTime(time)
+    }
+}
+
+/// Type of the values in a column, the [`Value`] variant it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Integer,
+    /// Typed formats store `scale` digits after the decimal point, and
+    /// reject values with more
+    Decimal {
+        scale: u32,
+    },
+    Bool,
+    Date,
+    DateTime,
+}
+
+/// A named field of the flattened record
+pub struct Column {
+    pub name: &'static str,
+    pub kind: Kind,
+    value: fn(&VehicleRecord, &Catalogue) -> Option<Value>,
+}
+
+impl Column {
+    /// The column's value for `record`, looking up equipment types in
+    /// `catalogue`
+    pub fn value(&self, record: &VehicleRecord, catalogue: &Catalogue) -> Option<Value> {
+        (self.value)(record, catalogue)
+    }
+}
+
+const fn column(
+    name: &'static str,
+    kind: Kind,
+    value: fn(&VehicleRecord, &Catalogue) -> Option<Value>,
+) -> Column {
+    Column { name, kind, value }
+}
+
+/// Every column, in output order. Repeated structures are flattened:
+/// `fuel` and its neighbours describe the primary fuel, `fuels` and
+/// `equipment` list all entries separated by "; ".
+pub static COLUMNS: &[Column] = &[
+    column("ident", Kind::Integer, |r, _| Some(r.ident.into())),
+    column("kind_number", Kind::Integer, |r, _| number(&r.kind)),
+    column("kind", Kind::Text, |r, _| name(&r.kind)),
+    column("usage_number", Kind::Integer, |r, _| number(&r.usage)),
+    column("usage", Kind::Text, |r, _| name(&r.usage)),
+    column("plate", Kind::Text, |r, _| r.plate.clone().map(Value::from)),
+    column("plate_class", Kind::Text, |r, _| {
+        r.plate_class.map(|class| class.as_str().to_string().into())
+    }),
+    column("plate_expiry", Kind::Date, |r, _| {
+        r.plate_expiry.map(Value::from)
+    }),
+    column("registration_status", Kind::Text, |r, _| {
+        r.registration_status.as_ref().map(|s| s.to_string().into())
+    }),
+    column("registration_status_date", Kind::DateTime, |r, _| {
+        r.registration_status_date.map(Value::from)
+    }),
+    column("created_from", Kind::Text, |r, _| {
+        r.details.created_from.clone().map(Value::from)
+    }),
+    column("status", Kind::Text, |r, _| {
+        r.details.status.as_ref().map(|s| s.to_string().into())
+    }),
+    column("status_date", Kind::DateTime, |r, _| {
+        r.details.status_date.map(Value::from)
+    }),
+    column("first_registration", Kind::Date, |r, _| {
+        r.details.first_registration.map(Value::from)
+    }),
+    column("vin", Kind::Text, |r, _| {
+        r.details.vin.clone().map(Value::from)
+    }),
+    column("vin_location", Kind::Text, |r, _| {
+        r.details.vin_location.clone().map(Value::from)
+    }),
+    column("model_year", Kind::Integer, |r, _| {
+        r.details.model_year.map(Value::from)
+    }),
+    column("total_weight", Kind::Integer, |r, _| {
+        r.details.total_weight.map(Value::from)
+    }),
+    column("technical_total_weight", Kind::Integer, |r, _| {
+        r.details.technical_total_weight.map(Value::from)
+    }),
+    column("curb_weight_min", Kind::Integer, |r, _| {
+        r.details.curb_weight_min.map(Value::from)
+    }),
+    column("curb_weight_max", Kind::Integer, |r, _| {
+        r.details.curb_weight_max.map(Value::from)
+    }),
+    column("train_weight", Kind::Integer, |r, _| {
+        r.details.train_weight.map(Value::from)
+    }),
+    column("axles", Kind::Integer, |r, _| {
+        r.details.axles.map(Value::from)
+    }),
+    column("driving_axles", Kind::Integer, |r, _| {
+        r.details.driving_axles.map(Value::from)
+    }),
+    column("seats_min", Kind::Integer, |r, _| {
+        r.details.seats_min.map(Value::from)
+    }),
+    column("seats_max", Kind::Integer, |r, _| {
+        r.details.seats_max.map(Value::from)
+    }),
+    column("doors", Kind::Integer, |r, _| {
+        r.details.doors.map(Value::from)
+    }),
+    column("coupling_possible", Kind::Bool, |r, _| {
+        r.details.coupling_possible.map(Value::from)
+    }),
+    column("coupling_weight_unbraked", Kind::Integer, |r, _| {
+        r.details.coupling_weight_unbraked.map(Value::from)
+    }),
+    column("coupling_weight_braked", Kind::Integer, |r, _| {
+        r.details.coupling_weight_braked.map(Value::from)
+    }),
+    column("ncap_test", Kind::Bool, |r, _| {
+        r.details.ncap_test.map(Value::from)
+    }),
+    column("condition", Kind::Text, |r, _| {
+        r.details.condition.clone().map(Value::from)
+    }),
+    column("taxi_suitable", Kind::Bool, |r, _| {
+        r.details.taxi_suitable.map(Value::from)
+    }),
+    column("traffic_damage", Kind::Bool, |r, _| {
+        r.details.traffic_damage.map(Value::from)
+    }),
+    column("type_notification_number", Kind::Text, |r, _| {
+        r.details.type_notification_number.clone().map(Value::from)
+    }),
+    column("type_approval_number", Kind::Text, |r, _| {
+        r.details.type_approval_number.clone().map(Value::from)
+    }),
+    column("comment", Kind::Text, |r, _| {
+        r.details.comment.clone().map(Value::from)
+    }),
+    column("make_number", Kind::Integer, |r, _| {
+        number(&r.details.designation.make)
+    }),
+    column("make", Kind::Text, |r, _| name(&r.details.designation.make)),
+    column("model_number", Kind::Integer, |r, _| {
+        number(&r.details.designation.model)
+    }),
+    column("model", Kind::Text, |r, _| {
+        name(&r.details.designation.model)
+    }),
+    column("variant_number", Kind::Integer, |r, _| {
+        number(&r.details.designation.variant)
+    }),
+    column("variant", Kind::Text, |r, _| {
+        name(&r.details.designation.variant)
+    }),
+    column("vehicle_type_number", Kind::Integer, |r, _| {
+        number(&r.details.designation.vehicle_type)
+    }),
+    column("vehicle_type", Kind::Text, |r, _| {
+        name(&r.details.designation.vehicle_type)
+    }),
+    column("colour_number", Kind::Integer, |r, _| {
+        number(&r.details.colour)
+    }),
+    column("colour", Kind::Text, |r, _| name(&r.details.colour)),
+    column("body_type_number", Kind::Integer, |r, _| {
+        number(&r.details.body_type)
+    }),
+    column("body_type", Kind::Text, |r, _| name(&r.details.body_type)),
+    column("norm_number", Kind::Integer, |r, _| number(&r.details.norm)),
+    column("norm", Kind::Text, |r, _| name(&r.details.norm)),
+    column("particle_filter", Kind::Bool, |r, _| {
+        r.details.environment.particle_filter.map(Value::from)
+    }),
+    column("co2_emission", Kind::Decimal { scale: 1 }, |r, _| {
+        r.details.environment.co2_emission.map(Value::from)
+    }),
+    column("cylinders", Kind::Integer, |r, _| {
+        r.details.motor.cylinders.map(Value::from)
+    }),
+    column("displacement", Kind::Decimal { scale: 1 }, |r, _| {
+        r.details.motor.displacement.map(Value::from)
+    }),
+    column("displacement_unavailable", Kind::Bool, |r, _| {
+        r.details.motor.displacement_unavailable.map(Value::from)
+    }),
+    column("max_power", Kind::Decimal { scale: 1 }, |r, _| {
+        r.details.motor.max_power.map(Value::from)
+    }),
+    column("max_power_unavailable", Kind::Bool, |r, _| {
+        r.details.motor.max_power_unavailable.map(Value::from)
+    }),
+    column("odometer", Kind::Integer, |r, _| {
+        r.details.motor.odometer.map(Value::from)
+    }),
+    column("odometer_documented", Kind::Bool, |r, _| {
+        r.details.motor.odometer_documented.map(Value::from)
+    }),
+    column("odometer_unavailable", Kind::Bool, |r, _| {
+        r.details.motor.odometer_unavailable.map(Value::from)
+    }),
+    column("innovative_technology", Kind::Bool, |r, _| {
+        r.details.motor.innovative_technology.map(Value::from)
+    }),
+    column("fuel_number", Kind::Integer, |r, _| {
+        primary_fuel(r).and_then(|f| f.drive_type.number.map(Value::from))
+    }),
+    column("fuel", Kind::Text, |r, _| {
+        primary_fuel(r).and_then(|f| f.drive_type.name.clone().map(Value::from))
+    }),
+    column("km_per_liter", Kind::Decimal { scale: 3 }, |r, _| {
+        primary_fuel(r).and_then(|f| f.km_per_liter.map(Value::from))
+    }),
+    column(
+        "electric_consumption",
+        Kind::Decimal { scale: 3 },
+        |r, _| primary_fuel(r).and_then(|f| f.electric_consumption.map(Value::from)),
+    ),
+    column("drivetrain", Kind::Text, |r, _| {
+        r.details
+            .motor
+            .drivetrain
+            .map(|drivetrain| drivetrain.as_str().to_string().into())
+    }),
+    column("fuels", Kind::Text, |r, _| {
+        list(
+            r.details
+                .motor
+                .fuels
+                .iter()
+                .filter_map(|f| f.drive_type.name.clone()),
+        )
+    }),
+    column("equipment", Kind::Text, |r, catalogue| {
+        list(r.details.equipment.iter().filter_map(|e| {
+            let name = catalogue.get(e.type_number?)?.name.clone()?;
+            Some(match e.count {
+                Some(count) => format!("{} ({})", name, count),
+                None => name,
+            })
+        }))
+    }),
+    column("inspection_kind", Kind::Text, |r, _| {
+        r.inspection
+            .as_ref()
+            .and_then(|i| i.kind.clone().map(Value::from))
+    }),
+    column("inspection_date", Kind::Date, |r, _| {
+        r.inspection.as_ref().and_then(|i| i.date.map(Value::from))
+    }),
+    column("inspection_result", Kind::Text, |r, _| {
+        r.inspection
+            .as_ref()
+            .and_then(|i| i.result.clone().map(Value::from))
+    }),
+    column("inspection_status", Kind::Text, |r, _| {
+        r.inspection
+            .as_ref()
+            .and_then(|i| i.status.clone().map(Value::from))
+    }),
+    column("inspection_status_date", Kind::Date, |r, _| {
+        r.inspection
+            .as_ref()
+            .and_then(|i| i.status_date.map(Value::from))
+    }),
+    column("inspection_due", Kind::Date, |r, _| {
+        inspection::due_date(r).map(Value::from)
+    }),
+];
+
+/// The columns named in a comma-separated list, in the order given, or
+/// all of them for `None`. The list must name at least one column.
+pub fn select_columns(names: Option<&str>) -> Result<Vec<&'static Column>, String> {
+    let Some(names) = names else {
+        return Ok(COLUMNS.iter().collect());
+    };
+    let columns = names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            COLUMNS.iter().find(|c| c.name == name).ok_or_else(|| {
+                let known: Vec<&str> = COLUMNS.iter().map(|c| c.name).collect();
+                format!(
+                    "unknown column {:?}, expected one of: {}",
+                    name,
+                    known.join(", ")
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.is_empty() {
+        return Err("--columns needs at least one column name".to_string());
+    }
+    Ok(columns)
+}
+
+fn number(coded: &Option<Coded>) -> Option<Value> {
+    coded.as_ref()?.number.map(Value::from)
+}
+
+fn name(coded: &Option<Coded>) -> Option<Value> {
+    coded.as_ref()?.name.clone().map(Value::from)
+}
+
+/// The fuel marked primary, or the first one listed
+fn primary_fuel(record: &VehicleRecord) -> Option<&Fuel> {
+    let fuels = &record.details.motor.fuels;
+    fuels
+        .iter()
+        .find(|f| f.primary == Some(true))
+        .or_else(|| fuels.first())
+}
+
+fn list(items: impl Iterator<Item = String>) -> Option<Value> {
+    let items: Vec<String> = items.collect();
+    (!items.is_empty()).then(|| Value::Text(items.join("; ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(columns: &[&Column]) -> Vec<&'static str> {
+        columns.iter().map(|column| column.name).collect()
+    }
+
+    fn error(names: &str) -> String {
+        match select_columns(Some(names)) {
+            Ok(columns) => panic!("expected an error, got {:?}", self::names(&columns)),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn columns_in_the_order_given() {
+        let columns = select_columns(Some("vin, plate,ident,,make")).unwrap();
+        assert_eq!(names(&columns), ["vin", "plate", "ident", "make"]);
+        assert_eq!(select_columns(None).unwrap().len(), COLUMNS.len());
+    }
+
+    #[test]
+    fn unknown_columns_are_rejected() {
+        let message = error("plate,colour,registration");
+        assert!(
+            message.starts_with("unknown column \"registration\", expected one of: ident, "),
+            "{}",
+            message
+        );
+        assert!(message.ends_with(", inspection_due"), "{}", message);
+        assert!(error("Plate").contains("\"Plate\""));
+    }
+
+    #[test]
+    fn columns_must_name_one() {
+        for names in ["", " ", ",,"] {
+            let message = error(names);
+            assert!(message.contains("at least one column"), "{}", message);
+        }
+    }
+}
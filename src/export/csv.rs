@@ -0,0 +1,130 @@
+//! RFC 4180 CSV with a header row.
+
+use std::io::{self, Write};
+
+use crate::equipment::Catalogue;
+use crate::export::{Column, RecordWriter};
+use crate::model::VehicleRecord;
+
+/// Writes one row per record with the chosen columns. Empty fields are
+/// left blank.
+pub struct CsvWriter<W: Write> {
+    out: W,
+    columns: Vec<&'static Column>,
+    line: String,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Start the file by writing the header row
+    pub fn new(out: W, columns: Vec<&'static Column>) -> io::Result<Self> {
+        let mut writer = CsvWriter {
+            out,
+            columns,
+            line: String::new(),
+        };
+        for (i, column) in writer.columns.iter().enumerate() {
+            if i > 0 {
+                writer.line.push(',');
+            }
+            push_field(&mut writer.line, column.name);
+        }
+        writer.end_line()?;
+        Ok(writer)
+    }
+
+    fn end_line(&mut self) -> io::Result<()> {
+        self.line.push_str("\r\n");
+        self.out.write_all(self.line.as_bytes())?;
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for CsvWriter<W> {
+    fn write(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()> {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                self.line.push(',');
+            }
+            if let Some(value) = column.value(record, catalogue) {
+                push_field(&mut self.line, &value.to_string());
+            }
+        }
+        self.end_line()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Quote fields holding a separator, a quote or a line break
+fn push_field(line: &mut String, field: &str) {
+    if field.contains([',', '"', '\r', '\n']) {
+        line.push('"');
+        line.push_str(&field.replace('"', "\"\""));
+        line.push('"');
+    } else {
+        line.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::select_columns;
+
+    fn csv(columns: &str, records: &[VehicleRecord]) -> String {
+        let columns = select_columns(Some(columns)).unwrap();
+        let mut writer = CsvWriter::new(Vec::new(), columns).unwrap();
+        for record in records {
+            writer.write(record, &Catalogue::new()).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(writer.out).unwrap()
+    }
+
+    fn with_comment(comment: &str) -> VehicleRecord {
+        let mut record = VehicleRecord {
+            ident: 7,
+            plate: Some("AB12345".to_string()),
+            ..VehicleRecord::default()
+        };
+        record.details.comment = Some(comment.to_string());
+        record
+    }
+
+    #[test]
+    fn quoting() {
+        for (comment, field) in [
+            ("plain text", "plain text"),
+            ("", ""),
+            ("a, b", "\"a, b\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("two\nlines", "\"two\nlines\""),
+            ("two\r\nlines", "\"two\r\nlines\""),
+            ("\"", "\"\"\"\""),
+        ] {
+            assert_eq!(
+                csv("ident,comment", &[with_comment(comment)]),
+                format!("ident,comment\r\n7,{}\r\n", field)
+            );
+        }
+    }
+
+    #[test]
+    fn header_and_columns_in_the_order_given() {
+        let mut record = with_comment("x");
+        record.details.vin = Some("WAUZZZ4F38N069602".to_string());
+        assert_eq!(
+            csv(
+                "vin,comment,ident,plate,make",
+                &[record, VehicleRecord::default()]
+            ),
+            "vin,comment,ident,plate,make\r\n\
+             WAUZZZ4F38N069602,x,7,AB12345,\r\n\
+             ,,0,,\r\n"
+        );
+        assert_eq!(csv("plate", &[]), "plate\r\n");
+    }
+}
@@ -0,0 +1,558 @@
+//! JSON output, either as one pretty-printed array or as newline-delimited
+//! JSON with one compact object per line. Records keep the nesting of the
+//! model; absent values are written as `null`.
+
+use std::fmt::{Display, Write as _};
+use std::io::{self, Write};
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::equipment::Catalogue;
+use crate::export::RecordWriter;
+use crate::fuel::{Drivetrain, FuelKind};
+use crate::model::{
+    Coded, Decimal, Designation, Environment, Equipment, EquipmentType, Fuel, Inspection, Motor,
+    Status, VehicleDetails, VehicleRecord,
+};
+use crate::plate::PlateClass;
+
+/// Writes records as a JSON array, or as NDJSON when `lines` is set
+pub struct JsonWriter<W: Write> {
+    out: W,
+    json: Json,
+    lines: bool,
+    count: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(mut out: W, lines: bool) -> io::Result<Self> {
+        if !lines {
+            out.write_all(b"[")?;
+        }
+        Ok(JsonWriter {
+            out,
+            json: Json {
+                out: String::new(),
+                catalogue: Catalogue::new(),
+                pretty: !lines,
+                // Array elements are indented one level
+                depth: usize::from(!lines),
+            },
+            lines,
+            count: 0,
+        })
+    }
+}
+
+impl<W: Write> RecordWriter for JsonWriter<W> {
+    fn write(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()> {
+        let json = &mut self.json;
+        json.out.clear();
+        json.catalogue.adopt(record, catalogue);
+        if !self.lines && self.count > 0 {
+            json.out.push(',');
+        }
+        json.newline();
+        record.to_json(json);
+        if self.lines {
+            json.out.push('\n');
+        }
+        self.count += 1;
+        self.out.write_all(json.out.as_bytes())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.lines {
+            if self.count > 0 {
+                self.out.write_all(b"\n")?;
+            }
+            self.out.write_all(b"]\n")?;
+        }
+        self.out.flush()
+    }
+}
+
+/// Text of the record being written
+struct Json {
+    out: String,
+    /// Equipment types of the records written so far
+    catalogue: Catalogue,
+    pretty: bool,
+    /// Nesting level, for indentation
+    depth: usize,
+}
+
+impl Json {
+    fn object(&mut self, fields: &[(&str, &dyn ToJson)]) {
+        self.out.push('{');
+        self.depth += 1;
+        for (i, (name, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline();
+            self.string(name);
+            self.out.push_str(if self.pretty { ": " } else { ":" });
+            value.to_json(self);
+        }
+        self.depth -= 1;
+        if !fields.is_empty() {
+            self.newline();
+        }
+        self.out.push('}');
+    }
+
+    fn array<T: ToJson>(&mut self, items: &[T]) {
+        self.out.push('[');
+        self.depth += 1;
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline();
+            item.to_json(self);
+        }
+        self.depth -= 1;
+        if !items.is_empty() {
+            self.newline();
+        }
+        self.out.push(']');
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c if c < ' ' => {
+                    let _ = write!(self.out, "\\u{:04x}", c as u32);
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+
+    /// A number or literal, written as is
+    fn raw(&mut self, value: impl Display) {
+        let _ = write!(self.out, "{}", value);
+    }
+
+    fn newline(&mut self) {
+        if self.pretty {
+            self.out.push('\n');
+            for _ in 0..self.depth {
+                self.out.push_str("  ");
+            }
+        }
+    }
+}
+
+trait ToJson {
+    fn to_json(&self, json: &mut Json);
+}
+
+impl ToJson for String {
+    fn to_json(&self, json: &mut Json) {
+        json.string(self);
+    }
+}
+
+impl ToJson for u32 {
+    fn to_json(&self, json: &mut Json) {
+        json.raw(self);
+    }
+}
+
+impl ToJson for u64 {
+    fn to_json(&self, json: &mut Json) {
+        json.raw(self);
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self, json: &mut Json) {
+        json.raw(self);
+    }
+}
+
+/// Written as a number with the digits of the dump
+impl ToJson for Decimal {
+    fn to_json(&self, json: &mut Json) {
+        json.raw(self);
+    }
+}
+
+impl ToJson for NaiveDate {
+    fn to_json(&self, json: &mut Json) {
+        json.string(&self.format("%Y-%m-%d").to_string());
+    }
+}
+
+impl ToJson for DateTime<FixedOffset> {
+    fn to_json(&self, json: &mut Json) {
+        json.string(&self.to_rfc3339());
+    }
+}
+
+impl ToJson for Status {
+    fn to_json(&self, json: &mut Json) {
+        json.string(self.as_dmr());
+    }
+}
+
+impl ToJson for Drivetrain {
+    fn to_json(&self, json: &mut Json) {
+        json.string(self.as_str());
+    }
+}
+
+impl ToJson for FuelKind {
+    fn to_json(&self, json: &mut Json) {
+        json.string(self.as_str());
+    }
+}
+
+impl ToJson for PlateClass {
+    fn to_json(&self, json: &mut Json) {
+        json.string(self.as_str());
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self, json: &mut Json) {
+        match self {
+            Some(value) => value.to_json(json),
+            None => json.raw("null"),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self, json: &mut Json) {
+        json.array(self);
+    }
+}
+
+impl ToJson for Coded {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[("number", &self.number), ("name", &self.name)]);
+    }
+}
+
+impl ToJson for VehicleRecord {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("ident", &self.ident),
+            ("kind", &self.kind),
+            ("usage", &self.usage),
+            ("plate", &self.plate),
+            ("plate_class", &self.plate_class),
+            ("plate_expiry", &self.plate_expiry),
+            ("registration_status", &self.registration_status),
+            ("registration_status_date", &self.registration_status_date),
+            ("details", &self.details),
+            ("inspection", &self.inspection),
+        ]);
+    }
+}
+
+impl ToJson for VehicleDetails {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("created_from", &self.created_from),
+            ("status", &self.status),
+            ("status_date", &self.status_date),
+            ("first_registration", &self.first_registration),
+            ("vin", &self.vin),
+            ("vin_location", &self.vin_location),
+            ("model_year", &self.model_year),
+            ("total_weight", &self.total_weight),
+            ("technical_total_weight", &self.technical_total_weight),
+            ("curb_weight_min", &self.curb_weight_min),
+            ("curb_weight_max", &self.curb_weight_max),
+            ("train_weight", &self.train_weight),
+            ("axles", &self.axles),
+            ("driving_axles", &self.driving_axles),
+            ("seats_min", &self.seats_min),
+            ("seats_max", &self.seats_max),
+            ("doors", &self.doors),
+            ("coupling_possible", &self.coupling_possible),
+            ("coupling_weight_unbraked", &self.coupling_weight_unbraked),
+            ("coupling_weight_braked", &self.coupling_weight_braked),
+            ("ncap_test", &self.ncap_test),
+            ("condition", &self.condition),
+            ("taxi_suitable", &self.taxi_suitable),
+            ("traffic_damage", &self.traffic_damage),
+            ("type_notification_number", &self.type_notification_number),
+            ("type_approval_number", &self.type_approval_number),
+            ("comment", &self.comment),
+            ("designation", &self.designation),
+            ("colour", &self.colour),
+            ("body_type", &self.body_type),
+            ("norm", &self.norm),
+            ("environment", &self.environment),
+            ("motor", &self.motor),
+            ("equipment", &self.equipment),
+        ]);
+    }
+}
+
+impl ToJson for Designation {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("make", &self.make),
+            ("model", &self.model),
+            ("variant", &self.variant),
+            ("vehicle_type", &self.vehicle_type),
+        ]);
+    }
+}
+
+impl ToJson for Environment {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("particle_filter", &self.particle_filter),
+            ("co2_emission", &self.co2_emission),
+        ]);
+    }
+}
+
+impl ToJson for Motor {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("cylinders", &self.cylinders),
+            ("displacement", &self.displacement),
+            ("displacement_unavailable", &self.displacement_unavailable),
+            ("max_power", &self.max_power),
+            ("max_power_unavailable", &self.max_power_unavailable),
+            ("odometer", &self.odometer),
+            ("odometer_documented", &self.odometer_documented),
+            ("odometer_unavailable", &self.odometer_unavailable),
+            ("innovative_technology", &self.innovative_technology),
+            ("fuels", &self.fuels),
+            ("drivetrain", &self.drivetrain),
+        ]);
+    }
+}
+
+impl ToJson for Fuel {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("drive_type", &self.drive_type),
+            ("kind", &self.kind()),
+            ("km_per_liter", &self.km_per_liter),
+            ("electric_consumption", &self.electric_consumption),
+            ("primary", &self.primary),
+        ]);
+    }
+}
+
+impl ToJson for Equipment {
+    /// The type is written out in full, as in the dump
+    fn to_json(&self, json: &mut Json) {
+        let equipment_type = self
+            .type_number
+            .and_then(|number| json.catalogue.get(number))
+            .cloned()
+            .unwrap_or_else(|| EquipmentType {
+                number: self.type_number,
+                ..EquipmentType::default()
+            });
+        json.object(&[("count", &self.count), ("equipment_type", &equipment_type)]);
+    }
+}
+
+impl ToJson for EquipmentType {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("number", &self.number),
+            ("name", &self.name),
+            ("shown_at_inspection", &self.shown_at_inspection),
+            ("shown_on_inquiry", &self.shown_on_inquiry),
+            (
+                "shown_on_standard_creation",
+                &self.shown_on_standard_creation,
+            ),
+        ]);
+    }
+}
+
+impl ToJson for Inspection {
+    fn to_json(&self, json: &mut Json) {
+        json.object(&[
+            ("kind", &self.kind),
+            ("date", &self.date),
+            ("result", &self.result),
+            ("status", &self.status),
+            ("status_date", &self.status_date),
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(value: &dyn ToJson, pretty: bool) -> String {
+        let mut json = Json {
+            out: String::new(),
+            catalogue: Catalogue::new(),
+            pretty,
+            depth: 0,
+        };
+        value.to_json(&mut json);
+        json.out
+    }
+
+    fn coded(number: u64, name: &str) -> Coded {
+        Coded {
+            number: Some(number),
+            name: Some(name.to_string()),
+        }
+    }
+
+    fn export(records: &[VehicleRecord], catalogue: &Catalogue, lines: bool) -> String {
+        let mut writer = JsonWriter::new(Vec::new(), lines).unwrap();
+        for record in records {
+            writer.write(record, catalogue).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(writer.out).unwrap()
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let text = "say \"hi\"\\\n\r\t\u{1}\u{1f} to Ærø for 5 €\u{7f}".to_string();
+        assert_eq!(
+            json(&text, false),
+            "\"say \\\"hi\\\"\\\\\\n\\r\\t\\u0001\\u001f to Ærø for 5 €\u{7f}\""
+        );
+        assert_eq!(json(&String::new(), false), "\"\"");
+    }
+
+    #[test]
+    fn decimals_keep_their_digits() {
+        for (decimal, text) in [
+            (Decimal::new(-5, 1), "-0.5"),
+            (Decimal::new(5, 3), "0.005"),
+            (Decimal::new(-20833, 3), "-20.833"),
+            (Decimal::new(27730, 1), "2773.0"),
+            (Decimal::new(0, 0), "0"),
+            ("007.50".parse().unwrap(), "7.50"),
+            ("-.5".parse().unwrap(), "-0.5"),
+        ] {
+            assert_eq!(json(&decimal, false), text);
+        }
+    }
+
+    #[test]
+    fn nested_values_and_null() {
+        let fuel = Fuel {
+            drive_type: coded(3, "El"),
+            electric_consumption: Some(Decimal::new(1780, 1)),
+            ..Fuel::default()
+        };
+        assert_eq!(
+            json(&fuel, false),
+            "{\"drive_type\":{\"number\":3,\"name\":\"El\"},\"kind\":\"electric\",\
+             \"km_per_liter\":null,\"electric_consumption\":178.0,\"primary\":null}"
+        );
+        assert_eq!(
+            json(&fuel, true),
+            "{
+  \"drive_type\": {
+    \"number\": 3,
+    \"name\": \"El\"
+  },
+  \"kind\": \"electric\",
+  \"km_per_liter\": null,
+  \"electric_consumption\": 178.0,
+  \"primary\": null
+}"
+        );
+        assert_eq!(json(&Vec::<Fuel>::new(), true), "[]");
+        assert_eq!(
+            json(&vec![coded(1, "A")], false),
+            "[{\"number\":1,\"name\":\"A\"}]"
+        );
+        assert_eq!(json(&None::<Coded>, true), "null");
+    }
+
+    #[test]
+    fn equipment_types_come_from_the_catalogue() {
+        let mut catalogue = Catalogue::new();
+        catalogue.insert(EquipmentType {
+            number: Some(9901),
+            name: Some("Airbags".to_string()),
+            ..EquipmentType::default()
+        });
+        let mut record = VehicleRecord::default();
+        record.details.equipment = vec![
+            Equipment {
+                count: Some(2),
+                type_number: Some(9901),
+            },
+            Equipment {
+                count: Some(1),
+                type_number: Some(1234),
+            },
+        ];
+        let text = export(&[record], &catalogue, true);
+        assert!(text.contains(
+            "\"equipment\":[{\"count\":2,\"equipment_type\":{\"number\":9901,\"name\":\"Airbags\","
+        ));
+        assert!(text.contains("{\"count\":1,\"equipment_type\":{\"number\":1234,\"name\":null,"));
+    }
+
+    #[test]
+    fn array_framing() {
+        let catalogue = Catalogue::new();
+        assert_eq!(export(&[], &catalogue, false), "[]\n");
+
+        let records = [
+            VehicleRecord {
+                ident: 1,
+                ..VehicleRecord::default()
+            },
+            VehicleRecord {
+                ident: 2,
+                ..VehicleRecord::default()
+            },
+        ];
+        let text = export(&records, &catalogue, false);
+        assert!(text.starts_with("[\n  {\n    \"ident\": 1,\n"), "{}", text);
+        assert!(text.contains("\n  },\n  {\n    \"ident\": 2,\n"));
+        assert!(text.ends_with("\n  }\n]\n"));
+        assert!(!text.contains(",\n]"));
+        assert_eq!(text.matches("\n  {").count(), 2);
+    }
+
+    #[test]
+    fn ndjson_framing() {
+        let catalogue = Catalogue::new();
+        assert_eq!(export(&[], &catalogue, true), "");
+
+        let records: Vec<_> = (1..=3)
+            .map(|ident| VehicleRecord {
+                ident,
+                plate: Some(format!("AB1234{}", ident)),
+                ..VehicleRecord::default()
+            })
+            .collect();
+        let text = export(&records, &catalogue, true);
+        assert!(text.ends_with("}\n"));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for (ident, line) in (1..).zip(&lines) {
+            let start = format!("{{\"ident\":{},\"kind\":null,", ident);
+            assert!(line.starts_with(&start), "{}", line);
+            assert!(line.ends_with("\"inspection\":null}"), "{}", line);
+            assert!(!line.contains('\n') && !line.contains("  "));
+        }
+    }
+}
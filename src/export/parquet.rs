@@ -0,0 +1,1259 @@
+//! Apache Parquet with one flat, typed column per entry of
+//! [`COLUMNS`](crate::export::COLUMNS).
+//!
+//! Records are buffered into row groups of [`ROW_GROUP_ROWS`] rows, and
+//! each group is written out as soon as it is full, so memory use does not
+//! grow with the size of the dump. Every column chunk is a single data page
+//! compressed with gzip. Text columns are dictionary-encoded unless their
+//! dictionary outgrows [`DICTIONARY_LIMIT`], which in practice leaves only
+//! near-unique columns such as `vin` in plain encoding. Decimal columns
+//! are INT64 with a DECIMAL annotation at the fixed scale of their
+//! [`Kind`].
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::equipment::Catalogue;
+use crate::export::{Column, Kind, RecordWriter, Value};
+use crate::model::{Decimal, VehicleRecord};
+
+/// Rows buffered before a row group is written
+pub const ROW_GROUP_ROWS: usize = 100_000;
+
+/// Largest dictionary page, in bytes, before a text column chunk falls
+/// back to plain encoding
+pub const DICTIONARY_LIMIT: usize = 1 << 20;
+
+const MAGIC: &[u8] = b"PAR1";
+const CREATED_BY: &str = concat!("autoplate version ", env!("CARGO_PKG_VERSION"));
+
+// Physical types
+const BOOLEAN: i32 = 0;
+const INT32: i32 = 1;
+const INT64: i32 = 2;
+const BYTE_ARRAY: i32 = 6;
+
+// Encodings
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const RLE_DICTIONARY: i32 = 8;
+
+/// Digits of an unscaled decimal that always fit an INT64
+const DECIMAL_PRECISION: i32 = 18;
+
+// Page types
+const DATA_PAGE: i32 = 0;
+const DICTIONARY_PAGE: i32 = 2;
+
+// Converted types, written besides the logical types for older readers
+const UTF8: i32 = 0;
+const DECIMAL: i32 = 5;
+const DATE: i32 = 6;
+const TIMESTAMP_MICROS: i32 = 10;
+
+const OPTIONAL: i32 = 1;
+const GZIP: i32 = 2;
+
+/// Writes the chosen columns of every record as one Parquet file
+pub struct ParquetWriter<W: Write> {
+    out: W,
+    /// Bytes written so far, for the offsets in the footer
+    offset: u64,
+    columns: Vec<ColumnBuffer>,
+    /// Rows buffered for the current row group
+    rows: usize,
+    row_groups: Vec<RowGroup>,
+}
+
+impl<W: Write> ParquetWriter<W> {
+    pub fn new(mut out: W, columns: Vec<&'static Column>) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        Ok(ParquetWriter {
+            out,
+            offset: MAGIC.len() as u64,
+            columns: columns.into_iter().map(ColumnBuffer::new).collect(),
+            rows: 0,
+            row_groups: Vec::new(),
+        })
+    }
+
+    fn write_row_group(&mut self) -> io::Result<()> {
+        let mut chunks = Vec::with_capacity(self.columns.len());
+        for column in &mut self.columns {
+            let start = self.offset;
+            let mut chunk = Chunk {
+                name: column.column.name,
+                physical: column.values.physical(),
+                encodings: vec![PLAIN, RLE],
+                num_values: column.defined.len() as i64,
+                uncompressed: 0,
+                compressed: 0,
+                data_page_offset: start,
+                dictionary_page_offset: None,
+            };
+
+            // Definition levels, then the values present
+            let mut page = Vec::new();
+            let mut levels = Vec::new();
+            encode_hybrid(column.defined.iter().map(|&d| u32::from(d)), 1, &mut levels);
+            page.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+            page.extend_from_slice(&levels);
+
+            let dictionary = match &column.values {
+                Values::Text(texts) => Dictionary::build(texts),
+                _ => None,
+            };
+            let encoding = match dictionary {
+                Some(dictionary) => {
+                    let (compressed, uncompressed) = write_page(
+                        &mut self.out,
+                        DICTIONARY_PAGE,
+                        dictionary.entries as i32,
+                        PLAIN,
+                        &dictionary.page,
+                    )?;
+                    chunk.dictionary_page_offset = Some(start);
+                    chunk.data_page_offset = start + compressed;
+                    chunk.compressed += compressed;
+                    chunk.uncompressed += uncompressed;
+                    chunk.encodings.push(RLE_DICTIONARY);
+
+                    let width = bit_width(dictionary.entries.saturating_sub(1) as u32);
+                    page.push(width);
+                    encode_hybrid(dictionary.indices.into_iter(), width, &mut page);
+                    RLE_DICTIONARY
+                }
+                None => {
+                    column.values.encode_plain(&mut page);
+                    PLAIN
+                }
+            };
+
+            let (compressed, uncompressed) = write_page(
+                &mut self.out,
+                DATA_PAGE,
+                column.defined.len() as i32,
+                encoding,
+                &page,
+            )?;
+            chunk.compressed += compressed;
+            chunk.uncompressed += uncompressed;
+            self.offset += chunk.compressed;
+            chunks.push(chunk);
+            column.clear();
+        }
+
+        self.row_groups.push(RowGroup {
+            num_rows: self.rows as i64,
+            chunks,
+        });
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn write_footer(&mut self) -> io::Result<()> {
+        let mut t = Thrift::new();
+        t.i32(1, 1);
+        t.list(2, STRUCT, self.columns.len() + 1);
+        t.element(|t| {
+            t.binary(4, b"schema");
+            t.i32(5, self.columns.len() as i32);
+        });
+        for column in &self.columns {
+            t.element(|t| write_schema_element(t, column));
+        }
+        let num_rows: i64 = self.row_groups.iter().map(|group| group.num_rows).sum();
+        t.i64(3, num_rows);
+        t.list(4, STRUCT, self.row_groups.len());
+        for group in &self.row_groups {
+            t.element(|t| write_row_group_meta(t, group));
+        }
+        t.binary(6, CREATED_BY.as_bytes());
+        t.stop();
+
+        self.out.write_all(&t.out)?;
+        self.out.write_all(&(t.out.len() as u32).to_le_bytes())?;
+        self.out.write_all(MAGIC)
+    }
+}
+
+impl<W: Write> RecordWriter for ParquetWriter<W> {
+    fn write(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()> {
+        for column in &mut self.columns {
+            column.push(record, catalogue)?;
+        }
+        self.rows += 1;
+        if self.rows == ROW_GROUP_ROWS {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.rows > 0 {
+            self.write_row_group()?;
+        }
+        self.write_footer()?;
+        self.out.flush()
+    }
+}
+
+/// The values of one column in the current row group
+struct ColumnBuffer {
+    column: &'static Column,
+    /// Whether each row has a value
+    defined: Vec<bool>,
+    values: Values,
+}
+
+enum Values {
+    Boolean(Vec<bool>),
+    /// Days since 1970-01-01
+    Date(Vec<i32>),
+    /// Integers, microseconds since 1970-01-01 UTC, or unscaled decimals
+    Int64(Vec<i64>),
+    Text(Vec<String>),
+}
+
+impl ColumnBuffer {
+    fn new(column: &'static Column) -> Self {
+        let values = match column.kind {
+            Kind::Text => Values::Text(Vec::new()),
+            Kind::Integer | Kind::Decimal { .. } | Kind::DateTime => Values::Int64(Vec::new()),
+            Kind::Bool => Values::Boolean(Vec::new()),
+            Kind::Date => Values::Date(Vec::new()),
+        };
+        ColumnBuffer {
+            column,
+            defined: Vec::new(),
+            values,
+        }
+    }
+
+    fn push(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()> {
+        let Some(value) = self.column.value(record, catalogue) else {
+            self.defined.push(false);
+            return Ok(());
+        };
+        match (&mut self.values, value) {
+            (Values::Text(values), Value::Text(text)) => values.push(text),
+            (Values::Int64(values), Value::Integer(n)) => values.push(n as i64),
+            (Values::Int64(values), Value::DateTime(time)) => values.push(time.timestamp_micros()),
+            (Values::Int64(values), Value::Decimal(d)) => {
+                let Kind::Decimal { scale } = self.column.kind else {
+                    unreachable!("decimal value in a column of {:?}", self.column.kind)
+                };
+                let unscaled = unscaled(d, scale).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} in column {} does not fit a decimal with {} digits after the point",
+                            d, self.column.name, scale
+                        ),
+                    )
+                })?;
+                values.push(unscaled)
+            }
+            (Values::Boolean(values), Value::Bool(b)) => values.push(b),
+            (Values::Date(values), Value::Date(date)) => values.push(days(date)),
+            (_, value) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("column {} holds {:?}", self.column.name, value),
+                ))
+            }
+        }
+        self.defined.push(true);
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.defined.clear();
+        match &mut self.values {
+            Values::Boolean(values) => values.clear(),
+            Values::Date(values) => values.clear(),
+            Values::Int64(values) => values.clear(),
+            Values::Text(values) => values.clear(),
+        }
+    }
+}
+
+impl Values {
+    fn physical(&self) -> i32 {
+        match self {
+            Values::Boolean(_) => BOOLEAN,
+            Values::Date(_) => INT32,
+            Values::Int64(_) => INT64,
+            Values::Text(_) => BYTE_ARRAY,
+        }
+    }
+
+    fn encode_plain(&self, out: &mut Vec<u8>) {
+        match self {
+            // Bit-packed, least significant bit first
+            Values::Boolean(values) => {
+                for chunk in values.chunks(8) {
+                    let bits = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |bits, (i, &b)| bits | (u8::from(b) << i));
+                    out.push(bits);
+                }
+            }
+            Values::Date(values) => {
+                for value in values {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Values::Int64(values) => {
+                for value in values {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Values::Text(values) => {
+                for value in values {
+                    push_byte_array(out, value);
+                }
+            }
+        }
+    }
+}
+
+/// Distinct values of a text column chunk, in order of first use
+struct Dictionary {
+    entries: usize,
+    /// The entries, plain-encoded
+    page: Vec<u8>,
+    /// Entry of each value
+    indices: Vec<u32>,
+}
+
+impl Dictionary {
+    /// `None` when the dictionary grows past [`DICTIONARY_LIMIT`]
+    fn build(values: &[String]) -> Option<Self> {
+        let mut positions: HashMap<&str, u32> = HashMap::new();
+        let mut page = Vec::new();
+        let mut indices = Vec::with_capacity(values.len());
+        for value in values {
+            let next = positions.len() as u32;
+            let index = *positions.entry(value).or_insert_with(|| {
+                push_byte_array(&mut page, value);
+                next
+            });
+            if page.len() > DICTIONARY_LIMIT {
+                return None;
+            }
+            indices.push(index);
+        }
+        Some(Dictionary {
+            entries: positions.len(),
+            page,
+            indices,
+        })
+    }
+}
+
+/// A column chunk already written, as described in the footer
+struct Chunk {
+    name: &'static str,
+    physical: i32,
+    encodings: Vec<i32>,
+    num_values: i64,
+    /// Sizes include the page headers
+    uncompressed: u64,
+    compressed: u64,
+    data_page_offset: u64,
+    dictionary_page_offset: Option<u64>,
+}
+
+struct RowGroup {
+    num_rows: i64,
+    chunks: Vec<Chunk>,
+}
+
+/// Write a page header and the gzipped page. Returns the compressed and
+/// uncompressed size, both including the header.
+fn write_page<W: Write>(
+    out: &mut W,
+    page_type: i32,
+    num_values: i32,
+    encoding: i32,
+    page: &[u8],
+) -> io::Result<(u64, u64)> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(page)?;
+    let compressed = encoder.finish()?;
+
+    let mut t = Thrift::new();
+    t.i32(1, page_type);
+    t.i32(2, page.len() as i32);
+    t.i32(3, compressed.len() as i32);
+    if page_type == DICTIONARY_PAGE {
+        t.structure(7, |t| {
+            t.i32(1, num_values);
+            t.i32(2, encoding);
+        });
+    } else {
+        t.structure(5, |t| {
+            t.i32(1, num_values);
+            t.i32(2, encoding);
+            t.i32(3, RLE);
+            t.i32(4, RLE);
+        });
+    }
+    t.stop();
+
+    out.write_all(&t.out)?;
+    out.write_all(&compressed)?;
+    Ok((
+        (t.out.len() + compressed.len()) as u64,
+        (t.out.len() + page.len()) as u64,
+    ))
+}
+
+fn write_schema_element(t: &mut Thrift, column: &ColumnBuffer) {
+    t.i32(1, column.values.physical());
+    t.i32(3, OPTIONAL);
+    t.binary(4, column.column.name.as_bytes());
+    let converted = match column.column.kind {
+        Kind::Text => Some(UTF8),
+        Kind::Date => Some(DATE),
+        Kind::DateTime => Some(TIMESTAMP_MICROS),
+        Kind::Decimal { .. } => Some(DECIMAL),
+        Kind::Integer | Kind::Bool => None,
+    };
+    if let Some(converted) = converted {
+        t.i32(6, converted);
+    }
+    if let Kind::Decimal { scale } = column.column.kind {
+        t.i32(7, scale as i32);
+        t.i32(8, DECIMAL_PRECISION);
+    }
+    match column.column.kind {
+        Kind::Text => t.structure(10, |t| t.structure(1, |_| {})),
+        Kind::Date => t.structure(10, |t| t.structure(6, |_| {})),
+        Kind::DateTime => t.structure(10, |t| {
+            t.structure(8, |t| {
+                t.bool(1, true);
+                t.structure(2, |t| t.structure(2, |_| {}));
+            })
+        }),
+        Kind::Decimal { scale } => t.structure(10, |t| {
+            t.structure(5, |t| {
+                t.i32(1, scale as i32);
+                t.i32(2, DECIMAL_PRECISION);
+            })
+        }),
+        Kind::Integer | Kind::Bool => {}
+    }
+}
+
+fn write_row_group_meta(t: &mut Thrift, group: &RowGroup) {
+    t.list(1, STRUCT, group.chunks.len());
+    for chunk in &group.chunks {
+        t.element(|t| {
+            let start = chunk
+                .dictionary_page_offset
+                .unwrap_or(chunk.data_page_offset);
+            t.i64(2, start as i64);
+            t.structure(3, |t| {
+                t.i32(1, chunk.physical);
+                t.list(2, I32, chunk.encodings.len());
+                for &encoding in &chunk.encodings {
+                    t.varint(zigzag(encoding.into()));
+                }
+                t.list(3, BINARY, 1);
+                t.varint(chunk.name.len() as u64);
+                t.out.extend_from_slice(chunk.name.as_bytes());
+                t.i32(4, GZIP);
+                t.i64(5, chunk.num_values);
+                t.i64(6, chunk.uncompressed as i64);
+                t.i64(7, chunk.compressed as i64);
+                t.i64(9, chunk.data_page_offset as i64);
+                if let Some(offset) = chunk.dictionary_page_offset {
+                    t.i64(11, offset as i64);
+                }
+            });
+        });
+    }
+    let total: u64 = group.chunks.iter().map(|chunk| chunk.uncompressed).sum();
+    t.i64(2, total as i64);
+    t.i64(3, group.num_rows);
+}
+
+fn push_byte_array(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Bits needed for `max`, at least one
+fn bit_width(max: u32) -> u8 {
+    (32 - max.leading_zeros()).max(1) as u8
+}
+
+/// The RLE / bit-packing hybrid used for levels and dictionary indices.
+/// Runs of eight or more equal values are run-length encoded, everything
+/// else is bit-packed in groups of eight.
+fn encode_hybrid(values: impl Iterator<Item = u32>, width: u8, out: &mut Vec<u8>) {
+    let values: Vec<u32> = values.collect();
+    let run_at = |i: usize| values[i..].iter().take_while(|&&v| v == values[i]).count();
+    let mut i = 0;
+    while i < values.len() {
+        let run = run_at(i);
+        if run >= 8 || i + run == values.len() {
+            push_varint(out, (run as u64) << 1);
+            let bytes = usize::from(width).div_ceil(8);
+            out.extend_from_slice(&values[i].to_le_bytes()[..bytes]);
+            i += run;
+            continue;
+        }
+
+        // Bit-pack groups of eight until a long run starts a group
+        let start = i;
+        while i < values.len() && (i == start || run_at(i) < 8) {
+            i += 8;
+        }
+        let end = i.min(values.len());
+        let groups = (i - start) / 8;
+        push_varint(out, ((groups as u64) << 1) | 1);
+        let mut buffer = 0u64;
+        let mut bits = 0;
+        // The last group is padded with zeros
+        let padding = std::iter::repeat_n(0, i - end);
+        for value in values[start..end].iter().copied().chain(padding) {
+            buffer |= u64::from(value) << bits;
+            bits += width;
+            while bits >= 8 {
+                out.push(buffer as u8);
+                buffer >>= 8;
+                bits -= 8;
+            }
+        }
+    }
+}
+
+/// `d` as a whole number of `10^-scale`, `None` when it has more digits
+/// after the point or too many before it
+fn unscaled(d: Decimal, scale: u32) -> Option<i64> {
+    let unscaled = if d.scale() <= scale {
+        d.mantissa()
+            .checked_mul(10i64.checked_pow(scale - d.scale())?)?
+    } else {
+        // Only trailing zeros may be dropped
+        let divisor = 10i64.checked_pow(d.scale() - scale)?;
+        (d.mantissa() % divisor == 0).then(|| d.mantissa() / divisor)?
+    };
+    (unscaled.unsigned_abs() < 10u64.pow(DECIMAL_PRECISION as u32)).then_some(unscaled)
+}
+
+fn days(date: NaiveDate) -> i32 {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date");
+    (date - epoch).num_days() as i32
+}
+
+// Thrift compact protocol types
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+/// Encoder for the Thrift compact protocol that Parquet metadata uses.
+/// Starts inside the top-level struct, which [`Thrift::stop`] ends.
+struct Thrift {
+    out: Vec<u8>,
+    /// Id of the previous field of each open struct
+    last: Vec<i16>,
+}
+
+impl Thrift {
+    fn new() -> Self {
+        Thrift {
+            out: Vec::new(),
+            last: vec![0],
+        }
+    }
+
+    fn field(&mut self, id: i16, field_type: u8) {
+        let last = self.last.last_mut().expect("field outside a struct");
+        let delta = id - *last;
+        *last = id;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | field_type);
+        } else {
+            self.out.push(field_type);
+            push_varint(&mut self.out, zigzag(id.into()));
+        }
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.varint(zigzag(value.into()));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.varint(zigzag(value));
+    }
+
+    fn bool(&mut self, id: i16, value: bool) {
+        self.field(id, if value { 1 } else { 2 });
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, BINARY);
+        self.varint(value.len() as u64);
+        self.out.extend_from_slice(value);
+    }
+
+    fn structure(&mut self, id: i16, fields: impl FnOnce(&mut Self)) {
+        self.field(id, STRUCT);
+        self.element(fields);
+    }
+
+    /// Header of a list; the elements follow
+    fn list(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | element_type);
+        } else {
+            self.out.push(0xf0 | element_type);
+            self.varint(len as u64);
+        }
+    }
+
+    /// A struct without a field header, as in a list
+    fn element(&mut self, fields: impl FnOnce(&mut Self)) {
+        self.last.push(0);
+        fields(self);
+        self.stop();
+    }
+
+    /// End the innermost struct
+    fn stop(&mut self) {
+        self.out.push(0);
+        self.last.pop();
+    }
+
+    fn varint(&mut self, value: u64) {
+        push_varint(&mut self.out, value);
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::Read;
+
+    use chrono::DateTime;
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::export::COLUMNS;
+    use crate::model::{Coded, Equipment, EquipmentType, Fuel, Status};
+
+    /// Written from `records()`; regenerate with AUTOPLATE_BLESS=1 after a
+    /// deliberate format change
+    const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/vehicles.parquet");
+
+    fn decode_hybrid(mut bytes: &[u8], width: u8, n: usize) -> Vec<u32> {
+        let mut values = Vec::new();
+        while values.len() < n {
+            let header = read_varint(&mut bytes);
+            if header & 1 == 0 {
+                let len = usize::from(width).div_ceil(8);
+                let mut value = [0; 4];
+                value[..len].copy_from_slice(&bytes[..len]);
+                bytes = &bytes[len..];
+                let run = (header >> 1) as usize;
+                values.extend(std::iter::repeat_n(u32::from_le_bytes(value), run));
+            } else {
+                let groups = (header >> 1) as usize;
+                let len = groups * usize::from(width);
+                let (packed, rest) = bytes.split_at(len);
+                bytes = rest;
+                for i in 0..groups * 8 {
+                    let value = (0..usize::from(width)).fold(0u32, |value, bit| {
+                        let at = i * usize::from(width) + bit;
+                        value | u32::from(packed[at / 8] >> (at % 8) & 1) << bit
+                    });
+                    values.push(value);
+                }
+            }
+        }
+        assert!(bytes.is_empty(), "{} bytes left over", bytes.len());
+        // Bit-packed groups are padded to eight values
+        values.truncate(n);
+        values
+    }
+
+    fn read_varint(bytes: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[0];
+            *bytes = &bytes[1..];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn hybrid(values: &[u32], width: u8) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_hybrid(values.iter().copied(), width, &mut out);
+        assert_eq!(
+            decode_hybrid(&out, width, values.len()),
+            values,
+            "width {}",
+            width
+        );
+        out
+    }
+
+    #[test]
+    fn hybrid_bytes() {
+        // Runs hold the value in whole bytes
+        assert_eq!(hybrid(&[1; 10], 1), [20, 1]);
+        assert_eq!(hybrid(&[300; 8], 9), [16, 0x2c, 0x01]);
+        // Bit-packed groups, least significant bit first
+        assert_eq!(hybrid(&[0, 1, 0, 1, 0, 1, 0, 1], 1), [3, 0b1010_1010]);
+        assert_eq!(hybrid(&[0, 1, 2, 3, 4, 5, 6, 7], 3), [3, 0x88, 0xc6, 0xfa]);
+        // A short tail is padded with zeros
+        assert_eq!(hybrid(&[1, 0, 1], 1), [3, 0b101]);
+        assert_eq!(hybrid(&[], 1), []);
+    }
+
+    #[test]
+    fn hybrid_width_zero() {
+        // A single-entry dictionary needs no bits: only run headers
+        assert_eq!(hybrid(&[0; 20], 0), [40]);
+        assert_eq!(hybrid(&[0], 0), [2]);
+    }
+
+    #[test]
+    fn hybrid_wide_values() {
+        for width in [9, 12, 17, 24, 31, 32] {
+            let max = u32::MAX >> (32 - width);
+            let values: Vec<u32> = (0..50).map(|i| max - i * 7 % 13).collect();
+            hybrid(&values, width);
+            hybrid(&[max; 9], width);
+            let mut mixed = vec![max; 3];
+            mixed.extend([0; 8]);
+            mixed.extend([1, max]);
+            hybrid(&mixed, width);
+        }
+    }
+
+    #[test]
+    fn hybrid_run_boundaries() {
+        let run = |value, len| std::iter::repeat_n(value, len);
+        let cases: Vec<Vec<u32>> = vec![
+            // Seven equal values are bit-packed, eight are a run
+            run(1, 7).chain([0]).collect(),
+            run(1, 8).chain([0]).collect(),
+            // A run starting inside a bit-packed group
+            [0, 1, 0].into_iter().chain(run(1, 12)).collect(),
+            // A run at the start of the next group ends the packed part
+            (0..8).map(|i| i % 2).chain(run(1, 8)).chain([0]).collect(),
+            // Packed groups, a run, packed groups again
+            (0..16)
+                .map(|i| i % 3)
+                .chain(run(2, 20))
+                .chain((0..5).map(|i| i % 3))
+                .collect(),
+            // A short run at the end, after a packed group
+            (0..8).map(|i| i % 2).chain(run(1, 3)).collect(),
+            // Alternating runs of exactly eight
+            (0..64).map(|i| i / 8 % 2).collect(),
+            vec![1],
+        ];
+        for values in cases {
+            let needed = bit_width(values.iter().copied().max().unwrap_or(0));
+            for width in [1, 2, 3, 9].into_iter().filter(|&width| width >= needed) {
+                hybrid(&values, width);
+            }
+        }
+    }
+
+    #[test]
+    fn dictionary_indices_in_order_of_first_use() {
+        let values: Vec<String> = ["b", "a", "b", "c", "a"].map(String::from).into();
+        let dictionary = Dictionary::build(&values).unwrap();
+        assert_eq!(dictionary.entries, 3);
+        assert_eq!(dictionary.indices, [0, 1, 0, 2, 1]);
+        assert_eq!(dictionary.page, b"\x01\0\0\0b\x01\0\0\0a\x01\0\0\0c");
+    }
+
+    #[test]
+    fn dictionary_falls_back_past_the_limit() {
+        let distinct = DICTIONARY_LIMIT / 64 + 1;
+        let values: Vec<String> = (0..distinct).map(|i| format!("{:060}", i)).collect();
+        assert!(Dictionary::build(&values).is_none());
+        assert!(Dictionary::build(&values[..distinct - 1]).is_some());
+        // Repeats do not grow the dictionary
+        let repeated: Vec<String> = values[..10]
+            .iter()
+            .cycle()
+            .take(distinct * 2)
+            .cloned()
+            .collect();
+        assert_eq!(Dictionary::build(&repeated).unwrap().entries, 10);
+    }
+
+    #[test]
+    fn unscaled_decimals() {
+        assert_eq!(unscaled(Decimal::new(114, 1), 3), Some(11400));
+        assert_eq!(unscaled(Decimal::new(-5, 0), 1), Some(-50));
+        // Trailing zeros beyond the scale are dropped, other digits are not
+        assert_eq!(unscaled(Decimal::new(27730, 1), 0), Some(2773));
+        assert_eq!(unscaled(Decimal::new(11400, 3), 1), Some(114));
+        assert_eq!(unscaled(Decimal::new(11401, 3), 1), None);
+        // More digits than the precision
+        assert_eq!(
+            unscaled(Decimal::new(999_999_999_999_999_999, 0), 0),
+            Some(999_999_999_999_999_999)
+        );
+        assert_eq!(unscaled(Decimal::new(100_000_000_000_000_000, 0), 1), None);
+        assert_eq!(unscaled(Decimal::new(1, 0), 40), None);
+    }
+
+    #[test]
+    fn decimal_with_too_many_digits_is_rejected() {
+        let mut record = VehicleRecord::default();
+        record.details.motor.displacement = Some(Decimal::new(27735, 2));
+        let mut writer = ParquetWriter::new(Vec::new(), COLUMNS.iter().collect()).unwrap();
+        let error = writer.write(&record, &Catalogue::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("displacement"), "{}", error);
+    }
+
+    fn coded(number: u64, name: &str) -> Option<Coded> {
+        Some(Coded {
+            number: Some(number),
+            name: Some(name.to_string()),
+        })
+    }
+
+    /// Vehicles with values in most columns, missing ones, and repeats
+    fn records() -> Vec<VehicleRecord> {
+        let mut car = VehicleRecord {
+            ident: 1000000000000001,
+            kind: coded(1, "Personbil"),
+            plate: Some("AB12345".to_string()),
+            plate_expiry: NaiveDate::from_ymd_opt(2031, 3, 31),
+            registration_status: Some(Status::Registered),
+            registration_status_date: Some(
+                DateTime::parse_from_rfc3339("2019-06-03T10:15:00+02:00").unwrap(),
+            ),
+            ..VehicleRecord::default()
+        };
+        car.details.vin = Some("WVWZZZ1KZ6W000001".to_string());
+        car.details.first_registration = NaiveDate::from_ymd_opt(2006, 5, 1);
+        car.details.environment.particle_filter = Some(false);
+        car.details.environment.co2_emission = Some(Decimal::new(1634, 1));
+        car.details.motor.displacement = Some(Decimal::new(19680, 1));
+        car.details.motor.max_power = Some(Decimal::new(103, 0));
+        car.details.motor.fuels = vec![Fuel {
+            drive_type: coded(2, "Diesel").unwrap(),
+            km_per_liter: Some(Decimal::new(20833, 3)),
+            primary: Some(true),
+            ..Fuel::default()
+        }];
+        car.details.equipment = vec![
+            Equipment {
+                count: Some(2),
+                type_number: Some(9901),
+            },
+            // Not in the catalogue
+            Equipment {
+                count: Some(1),
+                type_number: Some(1234),
+            },
+        ];
+
+        let mut electric = car.clone();
+        electric.ident = 1000000000000002;
+        electric.plate = Some("EL10203".to_string());
+        electric.details.vin = Some("5YJ3E7EB0KF000002".to_string());
+        electric.details.environment.co2_emission = Some(Decimal::new(0, 0));
+        electric.details.motor.displacement = None;
+        electric.details.motor.fuels = vec![Fuel {
+            drive_type: coded(3, "El").unwrap(),
+            electric_consumption: Some(Decimal::new(1780, 1)),
+            ..Fuel::default()
+        }];
+
+        let scrapped = VehicleRecord {
+            ident: 1000000000000003,
+            kind: coded(1, "Personbil"),
+            registration_status: Some(Status::Scrapped),
+            ..VehicleRecord::default()
+        };
+        vec![car, electric, scrapped, VehicleRecord::default()]
+    }
+
+    fn catalogue() -> Catalogue {
+        let mut catalogue = Catalogue::new();
+        catalogue.insert(EquipmentType {
+            number: Some(9901),
+            name: Some("Airbags".to_string()),
+            ..EquipmentType::default()
+        });
+        catalogue
+    }
+
+    fn write(records: &[VehicleRecord]) -> Vec<u8> {
+        let mut writer = ParquetWriter::new(Vec::new(), COLUMNS.iter().collect()).unwrap();
+        for record in records {
+            writer.write(record, &catalogue()).unwrap();
+        }
+        writer.finish().unwrap();
+        writer.out
+    }
+
+    #[test]
+    fn golden_file_round_trip() {
+        let file = write(&records());
+        if std::env::var_os("AUTOPLATE_BLESS").is_some() {
+            std::fs::write(GOLDEN, &file).unwrap();
+        }
+        let golden = std::fs::read(GOLDEN).unwrap();
+        assert!(file == golden, "output differs from {}", GOLDEN);
+
+        let records = records();
+        let columns = read(&golden);
+        assert_eq!(columns.schema().len(), COLUMNS.len());
+        for (i, column) in COLUMNS.iter().enumerate() {
+            let element = &columns.schema()[i];
+            assert_eq!(
+                element.at(4),
+                &Field::Binary(column.name.as_bytes().to_vec())
+            );
+            assert_eq!(element.at(3).int(), i64::from(OPTIONAL));
+            let expected: Vec<Option<Stored>> = records
+                .iter()
+                .map(|record| {
+                    column
+                        .value(record, &catalogue())
+                        .map(|value| match (value, column.kind) {
+                            (Value::Decimal(d), Kind::Decimal { scale }) => {
+                                Stored::Int64(unscaled(d, scale).unwrap())
+                            }
+                            (value, _) => stored(value),
+                        })
+                })
+                .collect();
+            assert_eq!(columns.values[i], expected, "column {}", column.name);
+        }
+        let equipment = COLUMNS.iter().position(|c| c.name == "equipment").unwrap();
+        assert_eq!(
+            columns.values[equipment][0],
+            Some(Stored::Text("Airbags (2)".to_string()))
+        );
+    }
+
+    #[test]
+    fn decimal_annotations() {
+        let columns = read(&write(&records()));
+        for (column, element) in COLUMNS.iter().zip(columns.schema()) {
+            let Kind::Decimal { scale } = column.kind else {
+                assert!(element.get(7).is_none(), "{}", column.name);
+                continue;
+            };
+            assert_eq!(element.at(1).int(), i64::from(INT64));
+            assert_eq!(element.at(6).int(), i64::from(DECIMAL));
+            assert_eq!(element.at(7).int(), i64::from(scale));
+            assert_eq!(element.at(8).int(), i64::from(DECIMAL_PRECISION));
+            let logical = element.at(10).at(5);
+            assert_eq!(logical.at(1).int(), i64::from(scale));
+            assert_eq!(logical.at(2).int(), i64::from(DECIMAL_PRECISION));
+        }
+    }
+
+    #[test]
+    fn near_unique_text_falls_back_to_plain() {
+        let records: Vec<VehicleRecord> = (0..DICTIONARY_LIMIT / 64 + 1)
+            .map(|i| {
+                let mut record = VehicleRecord {
+                    ident: i as u64,
+                    kind: coded(1, "Personbil"),
+                    ..VehicleRecord::default()
+                };
+                record.details.vin = Some(format!("{:060}", i));
+                record
+            })
+            .collect();
+        let columns = read(&write(&records));
+        let index = |name| COLUMNS.iter().position(|c| c.name == name).unwrap();
+        assert_eq!(columns.encodings[index("vin")], [i64::from(PLAIN)]);
+        assert_eq!(
+            columns.encodings[index("kind")],
+            [i64::from(RLE_DICTIONARY)]
+        );
+        let vins = &columns.values[index("vin")];
+        assert_eq!(vins.len(), records.len());
+        assert_eq!(vins[12345], Some(Stored::Text(format!("{:060}", 12345))));
+    }
+
+    /// A value as stored, before logical types are applied
+    #[derive(Debug, Clone, PartialEq)]
+    enum Stored {
+        Boolean(bool),
+        Int32(i32),
+        Int64(i64),
+        Text(String),
+    }
+
+    fn stored(value: Value) -> Stored {
+        match value {
+            Value::Text(text) => Stored::Text(text),
+            Value::Integer(n) => Stored::Int64(n as i64),
+            Value::Decimal(d) => Stored::Int64(d.mantissa()),
+            Value::Bool(b) => Stored::Boolean(b),
+            Value::Date(date) => Stored::Int32(days(date)),
+            Value::DateTime(time) => Stored::Int64(time.timestamp_micros()),
+        }
+    }
+
+    /// Thrift compact values, enough of the protocol for the footer and
+    /// page headers
+    #[derive(Debug, PartialEq)]
+    enum Field {
+        Int(i64),
+        Binary(Vec<u8>),
+        Bool(bool),
+        List(Vec<Field>),
+        Struct(BTreeMap<i16, Field>),
+    }
+
+    impl Field {
+        fn int(&self) -> i64 {
+            match self {
+                Field::Int(n) => *n,
+                other => panic!("{:?} is not an integer", other),
+            }
+        }
+
+        fn list(&self) -> &[Field] {
+            match self {
+                Field::List(items) => items,
+                other => panic!("{:?} is not a list", other),
+            }
+        }
+
+        fn get(&self, id: i16) -> Option<&Field> {
+            match self {
+                Field::Struct(fields) => fields.get(&id),
+                other => panic!("{:?} is not a struct", other),
+            }
+        }
+
+        fn at(&self, id: i16) -> &Field {
+            self.get(id)
+                .unwrap_or_else(|| panic!("no field {} in {:?}", id, self))
+        }
+
+        fn read_struct(bytes: &mut &[u8]) -> Field {
+            let mut fields = BTreeMap::new();
+            let mut last = 0;
+            loop {
+                let header = bytes[0];
+                *bytes = &bytes[1..];
+                if header == 0 {
+                    return Field::Struct(fields);
+                }
+                last = match header >> 4 {
+                    0 => unzigzag(read_varint(bytes)) as i16,
+                    delta => last + i16::from(delta),
+                };
+                let value = match header & 0x0f {
+                    1 => Field::Bool(true),
+                    2 => Field::Bool(false),
+                    field_type => Field::read(bytes, field_type),
+                };
+                fields.insert(last, value);
+            }
+        }
+
+        fn read(bytes: &mut &[u8], field_type: u8) -> Field {
+            match field_type {
+                I32 | I64 => Field::Int(unzigzag(read_varint(bytes))),
+                BINARY => {
+                    let len = read_varint(bytes) as usize;
+                    let (value, rest) = bytes.split_at(len);
+                    *bytes = rest;
+                    Field::Binary(value.to_vec())
+                }
+                LIST => {
+                    let header = bytes[0];
+                    *bytes = &bytes[1..];
+                    let len = match header >> 4 {
+                        15 => read_varint(bytes) as usize,
+                        len => usize::from(len),
+                    };
+                    Field::List(
+                        (0..len)
+                            .map(|_| Field::read(bytes, header & 0x0f))
+                            .collect(),
+                    )
+                }
+                STRUCT => Field::read_struct(bytes),
+                other => panic!("unexpected Thrift type {}", other),
+            }
+        }
+    }
+
+    fn unzigzag(value: u64) -> i64 {
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+
+    /// Columns of a Parquet file as the writer lays them out: the schema
+    /// elements, and per column its encodings and values
+    struct Columns {
+        meta: Field,
+        encodings: Vec<Vec<i64>>,
+        values: Vec<Vec<Option<Stored>>>,
+    }
+
+    impl Columns {
+        fn schema(&self) -> &[Field] {
+            &self.meta.at(2).list()[1..]
+        }
+    }
+
+    fn read(file: &[u8]) -> Columns {
+        assert_eq!(&file[..4], MAGIC);
+        assert_eq!(&file[file.len() - 4..], MAGIC);
+        let footer_len =
+            u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap());
+        let mut footer = &file[file.len() - 8 - footer_len as usize..file.len() - 8];
+        let meta = Field::read_struct(&mut footer);
+        assert!(footer.is_empty());
+
+        let schema = &meta.at(2).list()[1..];
+        assert_eq!(meta.at(2).list()[0].at(5).int() as usize, schema.len());
+        let mut encodings = vec![Vec::new(); schema.len()];
+        let mut values = vec![Vec::new(); schema.len()];
+        let mut rows = 0;
+        for group in meta.at(4).list() {
+            let group_rows = group.at(3).int();
+            rows += group_rows;
+            for (i, chunk) in group.at(1).list().iter().enumerate() {
+                let chunk_meta = chunk.at(3);
+                let physical = chunk_meta.at(1).int() as i32;
+                assert_eq!(physical, schema[i].at(1).int() as i32);
+                let start = chunk_meta.get(11).unwrap_or(chunk_meta.at(9)).int() as usize;
+                assert_eq!(chunk.at(2).int() as usize, start);
+                let mut bytes = &file[start..start + chunk_meta.at(7).int() as usize];
+
+                let mut dictionary = None;
+                loop {
+                    let header = Field::read_struct(&mut bytes);
+                    let (compressed, rest) = bytes.split_at(header.at(3).int() as usize);
+                    bytes = rest;
+                    let mut page = Vec::new();
+                    GzDecoder::new(compressed).read_to_end(&mut page).unwrap();
+                    assert_eq!(page.len() as i64, header.at(2).int());
+
+                    if header.at(1).int() == i64::from(DICTIONARY_PAGE) {
+                        let entries = header.at(7).at(1).int() as usize;
+                        dictionary = Some(plain(&mut &page[..], physical, entries));
+                        continue;
+                    }
+                    let data = header.at(5);
+                    let num_values = data.at(1).int() as usize;
+                    assert_eq!(num_values as i64, group_rows);
+                    let levels_len = u32::from_le_bytes(page[..4].try_into().unwrap()) as usize;
+                    let defined = decode_hybrid(&page[4..4 + levels_len], 1, num_values);
+                    let mut rest = &page[4 + levels_len..];
+                    let present = defined.iter().filter(|&&d| d == 1).count();
+                    let encoding = data.at(2).int();
+                    encodings[i].push(encoding);
+                    let mut present_values = match (encoding as i32, &mut dictionary) {
+                        (RLE_DICTIONARY, Some(dictionary)) => {
+                            let width = rest[0];
+                            decode_hybrid(&rest[1..], width, present)
+                                .into_iter()
+                                .map(|index| dictionary[index as usize].clone())
+                                .collect()
+                        }
+                        (PLAIN, None) => {
+                            let values = plain(&mut rest, physical, present);
+                            assert!(rest.is_empty());
+                            values
+                        }
+                        (encoding, _) => panic!(
+                            "encoding {} with dictionary {:?}",
+                            encoding,
+                            dictionary.is_some()
+                        ),
+                    }
+                    .into_iter();
+                    values[i].extend(defined.iter().map(|&d| {
+                        if d == 1 {
+                            present_values.next()
+                        } else {
+                            None
+                        }
+                    }));
+                    break;
+                }
+                assert!(bytes.is_empty(), "column chunk size");
+            }
+        }
+        assert_eq!(meta.at(3).int(), rows);
+        Columns {
+            meta,
+            encodings,
+            values,
+        }
+    }
+
+    fn plain(bytes: &mut &[u8], physical: i32, count: usize) -> Vec<Stored> {
+        let mut take = |len: usize| {
+            let (value, rest) = bytes.split_at(len);
+            *bytes = rest;
+            value
+        };
+        match physical {
+            BOOLEAN => {
+                let packed = take(count.div_ceil(8));
+                (0..count)
+                    .map(|i| Stored::Boolean(packed[i / 8] >> (i % 8) & 1 == 1))
+                    .collect()
+            }
+            INT32 => (0..count)
+                .map(|_| Stored::Int32(i32::from_le_bytes(take(4).try_into().unwrap())))
+                .collect(),
+            INT64 => (0..count)
+                .map(|_| Stored::Int64(i64::from_le_bytes(take(8).try_into().unwrap())))
+                .collect(),
+            BYTE_ARRAY => (0..count)
+                .map(|_| {
+                    let len = u32::from_le_bytes(take(4).try_into().unwrap()) as usize;
+                    Stored::Text(String::from_utf8(take(len).to_vec()).unwrap())
+                })
+                .collect(),
+            other => panic!("physical type {}", other),
+        }
+    }
+}
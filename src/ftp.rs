@@ -0,0 +1,579 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime};
+use suppaftp::types::{FileType, Mode};
+use suppaftp::{FtpError, FtpResult, NativeTlsConnector, NativeTlsFtpStream};
+use zip::ZipArchive;
+
+use crate::archive::glob_match;
+use crate::cache::{is_downloaded, PARTIAL_SUFFIX};
+use crate::config::{FtpConfig, FtpMode, FtpTls};
+use crate::error::{Error, Result};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Longest wait between two download attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Why a download attempt failed
+#[derive(Debug)]
+enum Failure {
+    /// Worth another attempt: network trouble, a busy server, a short file
+    Transient(BoxError),
+    /// Retrying will not help: login refused, missing directory, no archives
+    Permanent(BoxError),
+}
+
+impl Failure {
+    fn permanent(error: impl Into<BoxError>) -> Self {
+        Failure::Permanent(error.into())
+    }
+}
+
+impl From<FtpError> for Failure {
+    fn from(error: FtpError) -> Self {
+        match &error {
+            // 5xx replies are permanent negative completions (RFC 959)
+            FtpError::UnexpectedResponse(response) if response.status.code() >= 500 => {
+                Failure::Permanent(error.into())
+            }
+            FtpError::InvalidAddress(_) => Failure::Permanent(error.into()),
+            // A refused certificate stays refused
+            FtpError::SecureError(_) => Failure::Permanent(error.into()),
+            _ => Failure::Transient(error.into()),
+        }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Failure::Transient(error.into())
+    }
+}
+
+// ProgressReader wraps a reader and reports progress
+struct ProgressReader<R: Read> {
+    reader: R,
+    total: u64,
+    current: u64,
+    last_print: u64,
+}
+
+impl<R: Read> ProgressReader<R> {
+    /// `current` is the number of bytes already on disk when resuming
+    fn new(reader: R, current: u64, total: u64) -> Self {
+        Self {
+            reader,
+            total,
+            current,
+            last_print: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.current += n as u64;
+
+        // Print progress every 1%
+        if let Some(percent_done) = (self.current * 100).checked_div(self.total) {
+            if percent_done > self.last_print {
+                self.last_print = percent_done;
+                print!(
+                    "\rDownloading: {}% ({} / {} bytes)",
+                    percent_done, self.current, self.total
+                );
+                io::stdout().flush().ok();
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+/// A zip archive in the remote directory
+#[derive(Debug, Clone)]
+struct RemoteFile {
+    name: String,
+    size: u64,
+    modified: Option<NaiveDateTime>,
+}
+
+/// Download the newest archive on the configured server into
+/// `download_dir`, returning its remote name and local path. Nothing is
+/// transferred when the archive is already there with the listed size.
+///
+/// The data goes to `<name>.part` first. A partial file left by an
+/// interrupted run is resumed with REST, and the file only gets its final
+/// name once its size matches the LIST size and every zip entry passes its
+/// CRC check.
+///
+/// Transient failures are retried up to `config.retries` times, waiting
+/// `config.retry_delay` and then twice as long after each further failure.
+/// Each retry resumes where the previous attempt stopped.
+pub fn download_from_ftp(config: &FtpConfig, download_dir: &Path) -> Result<(String, PathBuf)> {
+    let mut delay = config.retry_delay;
+    let mut attempt = 1;
+    loop {
+        match try_download(config, download_dir) {
+            Ok(downloaded) => return Ok(downloaded),
+            Err(Failure::Permanent(source)) => {
+                return Err(Error::Ftp {
+                    transient: false,
+                    source,
+                })
+            }
+            Err(Failure::Transient(source)) if attempt > config.retries => {
+                return Err(Error::Ftp {
+                    transient: true,
+                    source,
+                })
+            }
+            Err(Failure::Transient(e)) => {
+                eprintln!(
+                    "\nWarning: attempt {} of {} failed: {}; retrying in {}s",
+                    attempt,
+                    config.retries + 1,
+                    e,
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Connect to the server, honouring the connect and read timeouts, and
+/// switch to TLS as `config.tls` asks. suppaftp has no connect timeout for
+/// implicit FTPS, so only the read timeout applies there.
+fn connect(config: &FtpConfig) -> Result<NativeTlsFtpStream, Failure> {
+    let mut last_error = None;
+    for addr in (config.host.as_str(), config.port).to_socket_addrs()? {
+        let connected = match config.tls {
+            FtpTls::Off | FtpTls::Explicit => {
+                NativeTlsFtpStream::connect_timeout(addr, config.connect_timeout)
+            }
+            FtpTls::Implicit => {
+                NativeTlsFtpStream::connect_secure_implicit(addr, tls_connector()?, &config.host)
+            }
+        };
+        match connected {
+            Ok(mut ftp_stream) => {
+                ftp_stream
+                    .get_ref()
+                    .set_read_timeout(Some(config.read_timeout))?;
+                if config.tls == FtpTls::Explicit {
+                    ftp_stream = ftp_stream.into_secure(tls_connector()?, &config.host)?;
+                }
+                return Ok(ftp_stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => Failure::permanent(format!("{} has no addresses", config.host)),
+    })
+}
+
+/// TLS with the system's trusted certificates, checked against the host
+fn tls_connector() -> Result<NativeTlsConnector, Failure> {
+    native_tls::TlsConnector::new()
+        .map(NativeTlsConnector::from)
+        .map_err(Failure::permanent)
+}
+
+/// One download attempt; see `download_from_ftp`
+fn try_download(config: &FtpConfig, download_dir: &Path) -> Result<(String, PathBuf), Failure> {
+    // Connect to FTP server
+    let tls = match config.tls {
+        FtpTls::Off => "",
+        FtpTls::Explicit => ", explicit FTPS",
+        FtpTls::Implicit => ", implicit FTPS",
+    };
+    println!(
+        "Connecting to {}:{} ({} mode{})...",
+        config.host, config.port, config.mode, tls
+    );
+    let mut ftp_stream = connect(config)?;
+    ftp_stream.set_mode(match config.mode {
+        FtpMode::Passive => Mode::Passive,
+        FtpMode::Active => Mode::Active,
+    });
+    ftp_stream.login(&config.user, &config.password)?;
+
+    // Set binary transfer mode
+    ftp_stream.transfer_type(FileType::Binary)?;
+
+    // Change to target directory
+    ftp_stream.cwd(&config.directory)?;
+
+    // Find newest zip file
+    let newest = newest(list_archives(&mut ftp_stream, &config.pattern)?).ok_or_else(|| {
+        Failure::permanent(format!(
+            "No files matching {} found in {}",
+            config.pattern, config.directory
+        ))
+    })?;
+
+    fs::create_dir_all(download_dir).map_err(Failure::permanent)?;
+    let target = download_dir.join(&newest.name);
+    let partial = download_dir.join(format!("{}{}", newest.name, PARTIAL_SUFFIX));
+
+    if is_downloaded(&target, newest.size) {
+        println!("✓ Newest archive {} is already downloaded", newest.name);
+        let _ = ftp_stream.quit();
+        return Ok((newest.name, target));
+    }
+
+    match newest.modified {
+        Some(modified) => println!("Downloading: {} ({})", newest.name, modified),
+        None => println!("Downloading: {} (modification time unknown)", newest.name),
+    }
+    println!(
+        "File size: {:.2} MB",
+        newest.size as f64 / (1024.0 * 1024.0)
+    );
+
+    let mut offset = fs::metadata(&partial).map_or(0, |meta| meta.len());
+    if newest.size > 0 && offset > newest.size {
+        // Longer than the remote file, so not a prefix of it
+        offset = 0;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&partial)
+        .map_err(Failure::permanent)?;
+    file.set_len(offset).map_err(Failure::permanent)?;
+    file.seek(SeekFrom::End(0))?;
+
+    if newest.size == 0 || offset < newest.size {
+        if offset > 0 {
+            println!("Resuming at byte {}", offset);
+            ftp_stream.resume_transfer(offset as usize)?;
+        }
+
+        // Get a reader for the remote file
+        let reader = ftp_stream.retr_as_stream(&newest.name)?;
+        reader
+            .get_ref()
+            .set_read_timeout(Some(config.read_timeout))?;
+
+        // Create progress reader
+        let mut progress_reader = ProgressReader::new(reader, offset, newest.size);
+
+        // Stream download to the partial file with progress
+        let written = io::copy(&mut progress_reader, &mut file)?;
+
+        println!("\n✓ Downloaded {} bytes", written);
+
+        // Finalize the transfer
+        ftp_stream.finalize_retr_stream(progress_reader.reader)?;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    // Quit FTP connection
+    let _ = ftp_stream.quit();
+
+    verify_download(&partial, newest.size).map_err(Failure::Transient)?;
+    fs::rename(&partial, &target).map_err(Failure::permanent)?;
+
+    Ok((newest.name, target))
+}
+
+/// Check a finished download against the size from the listing and the CRC
+/// of every zip entry
+fn verify_download(path: &Path, expected_size: u64) -> Result<(), BoxError> {
+    let size = fs::metadata(path)?.len();
+    if expected_size > 0 && size != expected_size {
+        return Err(format!(
+            "downloaded {} bytes but the server lists {}",
+            size, expected_size
+        )
+        .into());
+    }
+
+    match check_zip(path) {
+        Ok(entries) => {
+            println!("✓ Verified size and CRC of {} entries", entries);
+            Ok(())
+        }
+        Err(e) => {
+            // Resuming would keep the bad bytes, so start over next time
+            fs::remove_file(path)?;
+            Err(format!("downloaded archive is corrupt: {}", e).into())
+        }
+    }
+}
+
+/// Read every entry to the end, which makes the zip reader compare its CRC
+fn check_zip(path: &Path) -> zip::result::ZipResult<usize> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        io::copy(&mut entry, &mut io::sink())?;
+    }
+    Ok(archive.len())
+}
+
+/// List the archives matching `pattern` in the current directory with the best
+/// modification time the server can give us.
+///
+/// MLSD is used when the server advertises it. Otherwise the LIST output is
+/// parsed and each archive's time is taken from MDTM, then from the LIST
+/// date, and finally from the timestamp in the archive's file name.
+fn list_archives(ftp_stream: &mut NativeTlsFtpStream, pattern: &str) -> FtpResult<Vec<RemoteFile>> {
+    let features = ftp_stream.feat().unwrap_or_default();
+
+    if features.contains_key("MLST") {
+        if let Ok(lines) = ftp_stream.mlsd(None) {
+            let archives: Vec<RemoteFile> = lines
+                .iter()
+                .filter_map(|line| parse_mlsd_line(line))
+                .filter(|file| glob_match(pattern, &file.name))
+                .collect();
+            if !archives.is_empty() {
+                return Ok(archives);
+            }
+        }
+    }
+
+    let today = Local::now().date_naive();
+    let mut archives = Vec::new();
+
+    for entry_line in ftp_stream.list(None)? {
+        let Some(mut file) = parse_list_line(&entry_line, today) else {
+            continue;
+        };
+        if !glob_match(pattern, &file.name) {
+            continue;
+        }
+
+        if features.contains_key("MDTM") {
+            if let Ok(modified) = ftp_stream.mdtm(&file.name) {
+                file.modified = Some(modified);
+            }
+        }
+        if file.modified.is_none() {
+            file.modified = parse_name_timestamp(&file.name);
+        }
+
+        archives.push(file);
+    }
+
+    Ok(archives)
+}
+
+/// The archive with the latest modification time. Archives without one
+/// count as oldest, and equal times are settled by the name.
+fn newest(archives: Vec<RemoteFile>) -> Option<RemoteFile> {
+    archives
+        .into_iter()
+        .max_by(|a, b| (a.modified, &a.name).cmp(&(b.modified, &b.name)))
+}
+
+/// Parse one MLSD fact line, e.g.
+/// `modify=20261102165603;size=1048576;type=file; ESStatistikListeModtag-20261102-165603.zip`
+fn parse_mlsd_line(line: &str) -> Option<RemoteFile> {
+    let (facts, name) = line.split_once(' ')?;
+
+    let mut file = RemoteFile {
+        name: name.to_string(),
+        size: 0,
+        modified: None,
+    };
+    for fact in facts.split(';') {
+        let Some((key, value)) = fact.split_once('=') else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "type" if !value.eq_ignore_ascii_case("file") => return None,
+            "size" => file.size = value.parse().unwrap_or(0),
+            "modify" => {
+                // Fractional seconds are optional
+                let stamp = value.split('.').next().unwrap_or(value);
+                file.modified = NaiveDateTime::parse_from_str(stamp, "%Y%m%d%H%M%S").ok();
+            }
+            _ => {}
+        }
+    }
+
+    file.modified = file.modified.or_else(|| parse_name_timestamp(&file.name));
+    Some(file)
+}
+
+/// Parse one Unix-style LIST line, e.g.
+/// `-rw-r--r--   1 ftp  ftp  1048576 Nov 02 16:56 ESStatistikListeModtag-20261102-165603.zip`
+///
+/// Recent files carry a time instead of a year. The year is then the one
+/// that puts the date at most a day ahead of `today`, which allows for
+/// servers running in a timezone ahead of ours.
+fn parse_list_line(line: &str, today: NaiveDate) -> Option<RemoteFile> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 9 || parts[0].starts_with('d') {
+        return None;
+    }
+
+    let name = parts[8..].join(" ");
+    let size = parts[4].parse().unwrap_or(0);
+
+    let month_day = format!("{} {}", parts[5], parts[6]);
+    let year_or_time = parts[7];
+    let modified = if year_or_time.contains(':') {
+        let this_year = format!("{} {} {}", month_day, today.year(), year_or_time);
+        NaiveDateTime::parse_from_str(&this_year, "%b %d %Y %H:%M")
+            .ok()
+            .map(|modified| {
+                if modified.date() > today + chrono::Duration::days(1) {
+                    modified.with_year(today.year() - 1).unwrap_or(modified)
+                } else {
+                    modified
+                }
+            })
+    } else {
+        NaiveDate::parse_from_str(&format!("{} {}", month_day, year_or_time), "%b %d %Y")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    };
+
+    Some(RemoteFile {
+        name,
+        size,
+        modified,
+    })
+}
+
+/// Extract the `YYYYMMDD-HHMMSS` stamp from an
+/// `ESStatistikListeModtag-YYYYMMDD-HHMMSS.zip` file name
+pub fn parse_name_timestamp(name: &str) -> Option<NaiveDateTime> {
+    let stamp = name
+        .strip_prefix("ESStatistikListeModtag-")?
+        .strip_suffix(".zip")?;
+    NaiveDateTime::parse_from_str(stamp, "%Y%m%d-%H%M%S").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(y, m, d).and_then(|date| date.and_hms_opt(h, min, s))
+    }
+
+    fn remote(name: &str, modified: Option<NaiveDateTime>) -> RemoteFile {
+        RemoteFile {
+            name: name.to_string(),
+            size: 0,
+            modified,
+        }
+    }
+
+    #[test]
+    fn mlsd_facts() {
+        let file = parse_mlsd_line(
+            "modify=20261102165603;size=1048576;type=file; ESStatistikListeModtag-20261102-165603.zip",
+        )
+        .unwrap();
+        assert_eq!(file.name, "ESStatistikListeModtag-20261102-165603.zip");
+        assert_eq!(file.size, 1048576);
+        assert_eq!(file.modified, at(2026, 11, 2, 16, 56, 3));
+
+        let file = parse_mlsd_line("Type=File;Modify=20261102165603.123;Size=7; a b.zip").unwrap();
+        assert_eq!(file.name, "a b.zip");
+        assert_eq!(file.size, 7);
+        assert_eq!(file.modified, at(2026, 11, 2, 16, 56, 3));
+    }
+
+    #[test]
+    fn mlsd_skips_directories() {
+        assert!(parse_mlsd_line("type=dir;modify=20261102165603; archive").is_none());
+        assert!(parse_mlsd_line("type=cdir; .").is_none());
+        assert!(parse_mlsd_line("no-facts").is_none());
+    }
+
+    #[test]
+    fn mlsd_falls_back_to_name() {
+        let file = parse_mlsd_line("type=file;size=1; ESStatistikListeModtag-20250101-010203.zip")
+            .unwrap();
+        assert_eq!(file.modified, at(2025, 1, 1, 1, 2, 3));
+        let file = parse_mlsd_line("type=file;modify=garbage; other.zip").unwrap();
+        assert_eq!(file.modified, None);
+    }
+
+    #[test]
+    fn list_with_year() {
+        let today = NaiveDate::from_ymd_opt(2026, 11, 3).unwrap();
+        let file = parse_list_line(
+            "-rw-r--r--   1 ftp  ftp  1048576 Nov 02  2024 ESStatistikListeModtag-20241102-165603.zip",
+            today,
+        )
+        .unwrap();
+        assert_eq!(file.name, "ESStatistikListeModtag-20241102-165603.zip");
+        assert_eq!(file.size, 1048576);
+        assert_eq!(file.modified, at(2024, 11, 2, 0, 0, 0));
+    }
+
+    #[test]
+    fn list_with_time_picks_year() {
+        let today = NaiveDate::from_ymd_opt(2026, 11, 2).unwrap();
+        let line = |date: &str| format!("-rw-r--r-- 1 ftp ftp 10 {} 16:56 dump.zip", date);
+
+        let file = parse_list_line(&line("Nov 02"), today).unwrap();
+        assert_eq!(file.modified, at(2026, 11, 2, 16, 56, 0));
+        // A server a timezone ahead may already be on tomorrow
+        let file = parse_list_line(&line("Nov 03"), today).unwrap();
+        assert_eq!(file.modified, at(2026, 11, 3, 16, 56, 0));
+        // Further ahead than that means last year
+        let file = parse_list_line(&line("Dec 24"), today).unwrap();
+        assert_eq!(file.modified, at(2025, 12, 24, 16, 56, 0));
+    }
+
+    #[test]
+    fn list_skips_directories_and_short_lines() {
+        let today = NaiveDate::from_ymd_opt(2026, 11, 2).unwrap();
+        assert!(parse_list_line("drwxr-xr-x 2 ftp ftp 4096 Nov 02 16:56 old", today).is_none());
+        assert!(parse_list_line("total 12", today).is_none());
+        let file =
+            parse_list_line("-rw-r--r-- 1 ftp ftp 10 Nov 02 16:56 with space.zip", today).unwrap();
+        assert_eq!(file.name, "with space.zip");
+    }
+
+    #[test]
+    fn name_timestamp() {
+        assert_eq!(
+            parse_name_timestamp("ESStatistikListeModtag-20261102-165603.zip"),
+            at(2026, 11, 2, 16, 56, 3)
+        );
+        assert_eq!(
+            parse_name_timestamp("ESStatistikListeModtag-latest.zip"),
+            None
+        );
+        assert_eq!(parse_name_timestamp("other-20261102-165603.zip"), None);
+    }
+
+    #[test]
+    fn newest_by_time_then_name() {
+        let archives = vec![
+            remote("b.zip", at(2026, 11, 1, 0, 0, 0)),
+            remote("c.zip", None),
+            remote("a.zip", at(2026, 11, 2, 0, 0, 0)),
+        ];
+        assert_eq!(newest(archives).unwrap().name, "a.zip");
+
+        let archives = vec![remote("a.zip", None), remote("b.zip", None)];
+        assert_eq!(newest(archives).unwrap().name, "b.zip");
+        assert!(newest(Vec::new()).is_none());
+    }
+}
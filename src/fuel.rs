@@ -0,0 +1,290 @@
+//! Fuel (DrivkraftType) and drivetrain classification.
+//!
+//! DMR lists every fuel a vehicle can run on as a DrivmiddelStruktur, so a
+//! plug-in hybrid carries both "Benzin" and "El". [`classify`] sums up the
+//! list as one [`Drivetrain`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::model::{Coded, Fuel};
+
+/// What a single DrivmiddelStruktur runs on, from its DrivkraftTypeNavn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FuelKind {
+    /// "Benzin"
+    Petrol,
+    /// "Diesel", including biodiesel
+    Diesel,
+    /// "El"
+    Electric,
+    /// "F-Gas", liquefied petroleum gas
+    Lpg,
+    /// "N-Gas", compressed natural gas or biogas
+    NaturalGas,
+    /// "Brint"
+    Hydrogen,
+    Other,
+}
+
+impl FuelKind {
+    pub fn of(drive_type: &Coded) -> FuelKind {
+        let Some(name) = &drive_type.name else {
+            return FuelKind::Other;
+        };
+        let name = name.trim().to_lowercase();
+        match name.as_str() {
+            "benzin" | "petrol" | "gasoline" => FuelKind::Petrol,
+            "el" | "elektricitet" | "electricity" => FuelKind::Electric,
+            "f-gas" | "lpg" | "autogas" => FuelKind::Lpg,
+            "n-gas" | "cng" | "lng" | "naturgas" | "biogas" => FuelKind::NaturalGas,
+            "brint" | "hydrogen" => FuelKind::Hydrogen,
+            _ if name.contains("diesel") => FuelKind::Diesel,
+            _ => FuelKind::Other,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FuelKind::Petrol => "petrol",
+            FuelKind::Diesel => "diesel",
+            FuelKind::Electric => "electric",
+            FuelKind::Lpg => "lpg",
+            FuelKind::NaturalGas => "natural-gas",
+            FuelKind::Hydrogen => "hydrogen",
+            FuelKind::Other => "other",
+        }
+    }
+
+    fn is_gas(self) -> bool {
+        matches!(self, FuelKind::Lpg | FuelKind::NaturalGas)
+    }
+}
+
+impl fmt::Display for FuelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a vehicle is powered, from all of its fuels together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Drivetrain {
+    Petrol,
+    Diesel,
+    /// Battery electric only
+    Electric,
+    /// A combustion fuel and "El" with a recorded electric consumption
+    PluginHybrid,
+    /// A combustion fuel and "El" without an electric consumption, i.e.
+    /// the battery is not charged from the grid
+    Hybrid,
+    /// LPG or natural gas, alone or besides petrol or diesel
+    Gas,
+    /// Fuel cell, possibly with a battery
+    Hydrogen,
+    Other,
+}
+
+/// Every drivetrain, in report order
+pub const DRIVETRAINS: &[Drivetrain] = &[
+    Drivetrain::Petrol,
+    Drivetrain::Diesel,
+    Drivetrain::Electric,
+    Drivetrain::PluginHybrid,
+    Drivetrain::Hybrid,
+    Drivetrain::Gas,
+    Drivetrain::Hydrogen,
+    Drivetrain::Other,
+];
+
+impl Drivetrain {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Drivetrain::Petrol => "petrol",
+            Drivetrain::Diesel => "diesel",
+            Drivetrain::Electric => "electric",
+            Drivetrain::PluginHybrid => "plug-in-hybrid",
+            Drivetrain::Hybrid => "hybrid",
+            Drivetrain::Gas => "gas",
+            Drivetrain::Hydrogen => "hydrogen",
+            Drivetrain::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for Drivetrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Drivetrain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase().replace('_', "-");
+        match s.as_str() {
+            "phev" | "plugin-hybrid" => return Ok(Drivetrain::PluginHybrid),
+            "ev" | "bev" => return Ok(Drivetrain::Electric),
+            _ => {}
+        }
+        DRIVETRAINS
+            .iter()
+            .copied()
+            .find(|drivetrain| drivetrain.as_str() == s)
+            .ok_or_else(|| {
+                let known: Vec<&str> = DRIVETRAINS.iter().map(|d| d.as_str()).collect();
+                format!(
+                    "unknown drivetrain {:?}, expected one of: {}",
+                    s,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+/// Classify a vehicle by its KoeretoejDrivmiddelSamling. `None` when no
+/// fuel is listed.
+pub fn classify(fuels: &[Fuel]) -> Option<Drivetrain> {
+    let primary = fuels
+        .iter()
+        .find(|fuel| fuel.primary == Some(true))
+        .or_else(|| fuels.first())?;
+    let kinds: Vec<FuelKind> = fuels.iter().map(Fuel::kind).collect();
+    let electric = fuels.iter().find(|fuel| fuel.kind() == FuelKind::Electric);
+    let combustion = kinds
+        .iter()
+        .any(|kind| !matches!(kind, FuelKind::Electric | FuelKind::Hydrogen));
+
+    let drivetrain = if kinds.contains(&FuelKind::Hydrogen) {
+        Drivetrain::Hydrogen
+    } else if let Some(electric) = electric {
+        match (combustion, electric.electric_consumption) {
+            (false, _) => Drivetrain::Electric,
+            (true, Some(_)) => Drivetrain::PluginHybrid,
+            (true, None) => Drivetrain::Hybrid,
+        }
+    } else if kinds.iter().any(|kind| kind.is_gas()) {
+        Drivetrain::Gas
+    } else {
+        match primary.kind() {
+            FuelKind::Petrol => Drivetrain::Petrol,
+            FuelKind::Diesel => Drivetrain::Diesel,
+            _ => Drivetrain::Other,
+        }
+    };
+    Some(drivetrain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Decimal;
+
+    fn fuel(name: &str, primary: Option<bool>) -> Fuel {
+        Fuel {
+            drive_type: Coded {
+                number: None,
+                name: Some(name.to_string()),
+            },
+            primary,
+            ..Fuel::default()
+        }
+    }
+
+    /// "El" with an electric consumption in Wh/km
+    fn charged(consumption: i64) -> Fuel {
+        Fuel {
+            electric_consumption: Some(Decimal::new(consumption, 0)),
+            ..fuel("El", Some(false))
+        }
+    }
+
+    #[test]
+    fn single_fuel() {
+        assert_eq!(
+            classify(&[fuel("Benzin", Some(true))]),
+            Some(Drivetrain::Petrol)
+        );
+        assert_eq!(
+            classify(&[fuel("Diesel", Some(true))]),
+            Some(Drivetrain::Diesel)
+        );
+        assert_eq!(classify(&[charged(160)]), Some(Drivetrain::Electric));
+        assert_eq!(classify(&[fuel("El", None)]), Some(Drivetrain::Electric));
+        assert_eq!(
+            classify(&[fuel("Træpiller", None)]),
+            Some(Drivetrain::Other)
+        );
+    }
+
+    #[test]
+    fn hybrids_by_electric_consumption() {
+        let plugin = [fuel("Benzin", Some(true)), charged(158)];
+        assert_eq!(classify(&plugin), Some(Drivetrain::PluginHybrid));
+        let plugin = [fuel("Diesel", Some(true)), charged(190)];
+        assert_eq!(classify(&plugin), Some(Drivetrain::PluginHybrid));
+
+        let hybrid = [fuel("Benzin", Some(true)), fuel("El", Some(false))];
+        assert_eq!(classify(&hybrid), Some(Drivetrain::Hybrid));
+    }
+
+    #[test]
+    fn gas() {
+        assert_eq!(
+            classify(&[fuel("F-Gas", Some(true))]),
+            Some(Drivetrain::Gas)
+        );
+        let bifuel = [fuel("Benzin", Some(true)), fuel("N-Gas", Some(false))];
+        assert_eq!(classify(&bifuel), Some(Drivetrain::Gas));
+    }
+
+    #[test]
+    fn hydrogen() {
+        assert_eq!(
+            classify(&[fuel("Brint", Some(true))]),
+            Some(Drivetrain::Hydrogen)
+        );
+        // A fuel cell car with a battery is not a hybrid
+        let fuel_cell = [fuel("Brint", Some(true)), charged(10)];
+        assert_eq!(classify(&fuel_cell), Some(Drivetrain::Hydrogen));
+    }
+
+    #[test]
+    fn primary_fuel_decides() {
+        let fuels = [fuel("Benzin", Some(false)), fuel("Diesel", Some(true))];
+        assert_eq!(classify(&fuels), Some(Drivetrain::Diesel));
+        // Without a primary flag the first fuel counts
+        let fuels = [fuel("Diesel", None), fuel("Benzin", None)];
+        assert_eq!(classify(&fuels), Some(Drivetrain::Diesel));
+    }
+
+    #[test]
+    fn no_fuels() {
+        assert_eq!(classify(&[]), None);
+    }
+
+    #[test]
+    fn fuel_kind_names() {
+        let kind = |name: &str| FuelKind::of(&fuel(name, None).drive_type);
+        for (names, expected) in [
+            (&["Benzin", "petrol", "Gasoline"][..], FuelKind::Petrol),
+            (&["Diesel", "Biodiesel"], FuelKind::Diesel),
+            (&["El", "Elektricitet", "electricity"], FuelKind::Electric),
+            (&["F-Gas", "LPG", "autogas"], FuelKind::Lpg),
+            (
+                &["N-Gas", "CNG", "biogas", "Naturgas"],
+                FuelKind::NaturalGas,
+            ),
+            (&["Brint", "hydrogen"], FuelKind::Hydrogen),
+            (&["Træpiller", ""], FuelKind::Other),
+        ] {
+            for name in names {
+                assert_eq!(kind(name), expected, "{}", name);
+            }
+        }
+        assert_eq!(kind(" benzin "), FuelKind::Petrol);
+        assert_eq!(FuelKind::of(&Coded::default()), FuelKind::Other);
+    }
+}
@@ -0,0 +1,225 @@
+//! Periodic inspection (periodisk syn) deadlines.
+//!
+//! How often a vehicle must be inspected depends on its kind
+//! (KoeretoejArtNavn): cars and vans first four years after their first
+//! registration and every two years after that, lorries, buses and heavy
+//! trailers every year. Kinds without a rule here, such as motorcycles,
+//! mopeds, tractors and light trailers, are only inspected on special
+//! occasions like a change of owner and are never overdue.
+
+use chrono::{Months, NaiveDate};
+
+use crate::model::VehicleRecord;
+
+/// Inspection interval of a kind of vehicle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    /// Months from the first registration to the first inspection
+    pub first: u32,
+    /// Months from one inspection to the next
+    pub every: u32,
+}
+
+struct Rule {
+    kind: &'static str,
+    /// Applies only above this technical total weight, in kg
+    heavier_than: Option<u32>,
+    interval: Interval,
+}
+
+const CAR: Interval = Interval {
+    first: 48,
+    every: 24,
+};
+const YEARLY: Interval = Interval {
+    first: 12,
+    every: 12,
+};
+
+const RULES: &[Rule] = &[
+    Rule {
+        kind: "Personbil",
+        heavier_than: None,
+        interval: CAR,
+    },
+    Rule {
+        kind: "Varebil",
+        heavier_than: None,
+        interval: CAR,
+    },
+    Rule {
+        kind: "Lastbil",
+        heavier_than: None,
+        interval: YEARLY,
+    },
+    Rule {
+        kind: "Bus",
+        heavier_than: None,
+        interval: YEARLY,
+    },
+    Rule {
+        kind: "Påhængsvogn",
+        heavier_than: Some(3500),
+        interval: YEARLY,
+    },
+    Rule {
+        kind: "Sættevogn",
+        heavier_than: Some(3500),
+        interval: YEARLY,
+    },
+];
+
+/// The periodic inspection interval for a vehicle, `None` when its kind
+/// is not inspected periodically
+pub fn interval(record: &VehicleRecord) -> Option<Interval> {
+    let kind = record.kind.as_ref()?.name.as_deref()?.trim();
+    let details = &record.details;
+    let weight = details.technical_total_weight.or(details.total_weight);
+    RULES
+        .iter()
+        .find(|rule| {
+            rule.kind.eq_ignore_ascii_case(kind)
+                && rule
+                    .heavier_than
+                    .is_none_or(|limit| weight.is_some_and(|weight| weight > limit))
+        })
+        .map(|rule| rule.interval)
+}
+
+/// When the next periodic inspection is due: one interval after the
+/// latest inspection, or after the first registration for a vehicle not
+/// inspected since
+pub fn due_date(record: &VehicleRecord) -> Option<NaiveDate> {
+    let interval = interval(record)?;
+    let first_registration = record.details.first_registration?;
+    let last = record
+        .inspection
+        .as_ref()
+        .and_then(|inspection| inspection.date)
+        .filter(|date| *date >= first_registration);
+    match last {
+        Some(date) => date.checked_add_months(Months::new(interval.every)),
+        None => first_registration.checked_add_months(Months::new(interval.first)),
+    }
+}
+
+/// The missed deadline of a vehicle that is overdue for inspection on
+/// `today`
+pub fn overdue(record: &VehicleRecord, today: NaiveDate) -> Option<NaiveDate> {
+    due_date(record).filter(|due| *due < today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Coded, Inspection};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn vehicle(kind: &str, first_registration: NaiveDate) -> VehicleRecord {
+        let mut record = VehicleRecord {
+            kind: Some(Coded {
+                number: None,
+                name: Some(kind.to_string()),
+            }),
+            ..VehicleRecord::default()
+        };
+        record.details.first_registration = Some(first_registration);
+        record
+    }
+
+    fn inspected(mut record: VehicleRecord, on: NaiveDate) -> VehicleRecord {
+        record.inspection = Some(Inspection {
+            date: Some(on),
+            ..Inspection::default()
+        });
+        record
+    }
+
+    fn trailer(kind: &str, technical: Option<u32>, total: Option<u32>) -> VehicleRecord {
+        let mut record = vehicle(kind, date(2020, 3, 15));
+        record.details.technical_total_weight = technical;
+        record.details.total_weight = total;
+        record
+    }
+
+    #[test]
+    fn cars_and_vans_after_four_then_every_two_years() {
+        for kind in ["Personbil", "Varebil", "personbil"] {
+            let car = vehicle(kind, date(2019, 5, 31));
+            assert_eq!(interval(&car), Some(CAR), "{}", kind);
+            assert_eq!(due_date(&car), Some(date(2023, 5, 31)));
+
+            let car = inspected(car, date(2023, 5, 2));
+            assert_eq!(due_date(&car), Some(date(2025, 5, 2)));
+        }
+    }
+
+    #[test]
+    fn lorries_and_buses_every_year() {
+        for kind in ["Lastbil", "Bus"] {
+            let lorry = vehicle(kind, date(2022, 1, 31));
+            assert_eq!(interval(&lorry), Some(YEARLY), "{}", kind);
+            assert_eq!(due_date(&lorry), Some(date(2023, 1, 31)));
+
+            let lorry = inspected(lorry, date(2024, 2, 29));
+            assert_eq!(due_date(&lorry), Some(date(2025, 2, 28)));
+        }
+    }
+
+    #[test]
+    fn heavy_trailers_every_year() {
+        for kind in ["Påhængsvogn", "Sættevogn"] {
+            assert_eq!(interval(&trailer(kind, Some(3500), None)), None);
+            assert_eq!(interval(&trailer(kind, Some(3501), None)), Some(YEARLY));
+            // The technical total weight decides when given
+            assert_eq!(interval(&trailer(kind, Some(3500), Some(3600))), None);
+            // and the total weight otherwise
+            assert_eq!(interval(&trailer(kind, None, Some(3500))), None);
+            assert_eq!(interval(&trailer(kind, None, Some(3501))), Some(YEARLY));
+            assert_eq!(interval(&trailer(kind, None, None)), None);
+        }
+        let heavy = trailer("Påhængsvogn", Some(18000), None);
+        assert_eq!(due_date(&heavy), Some(date(2021, 3, 15)));
+    }
+
+    #[test]
+    fn kinds_without_a_rule() {
+        for kind in ["Motorcykel", "Knallert", "Traktor", "Campingvogn", ""] {
+            let record = inspected(vehicle(kind, date(2010, 1, 1)), date(2012, 1, 1));
+            assert_eq!(interval(&record), None, "{}", kind);
+            assert_eq!(due_date(&record), None);
+            assert_eq!(overdue(&record, date(2030, 1, 1)), None);
+        }
+        assert_eq!(interval(&VehicleRecord::default()), None);
+    }
+
+    #[test]
+    fn inspection_before_first_registration_is_ignored() {
+        // An import inspected abroad or at customs before it was registered
+        let car = inspected(vehicle("Personbil", date(2021, 6, 1)), date(2021, 5, 20));
+        assert_eq!(due_date(&car), Some(date(2025, 6, 1)));
+
+        // One on the day of the first registration counts
+        let car = inspected(vehicle("Personbil", date(2021, 6, 1)), date(2021, 6, 1));
+        assert_eq!(due_date(&car), Some(date(2023, 6, 1)));
+    }
+
+    #[test]
+    fn no_due_date_without_first_registration() {
+        let mut car = vehicle("Personbil", date(2021, 6, 1));
+        car.details.first_registration = None;
+        assert_eq!(due_date(&car), None);
+    }
+
+    #[test]
+    fn overdue_only_after_the_due_date() {
+        let car = vehicle("Personbil", date(2019, 5, 31));
+        let due = date(2023, 5, 31);
+        assert_eq!(overdue(&car, date(2023, 5, 30)), None);
+        assert_eq!(overdue(&car, due), None);
+        assert_eq!(overdue(&car, date(2023, 6, 1)), Some(due));
+    }
+}
@@ -0,0 +1,47 @@
+//! Danish motor register (DMR) statistics dumps: download, parse and store.
+//!
+//! SKAT publishes the register as zip archives of XML on an FTP server.
+//! [`ftp::download_from_ftp`] keeps a local copy of the newest one,
+//! [`archive::Records`] streams the vehicles out of an archive and
+//! [`store::Store`] keeps them on disk, indexed by plate and VIN.
+//!
+//! [`archive::ZipRecords`] and [`parser::XmlRecords`] read an archive or a
+//! bare XML document from any reader, on the calling thread.
+//!
+//! ```no_run
+//! use std::fs::File;
+//!
+//! use autoplate::archive::{Records, DEFAULT_ENTRY_PATTERN};
+//! use autoplate::parser::Recovery;
+//!
+//! let records = Records::open(
+//!     || File::open("ESStatistikListeModtag.zip"),
+//!     DEFAULT_ENTRY_PATTERN,
+//!     Recovery::Skip,
+//! )?;
+//! for record in records {
+//!     let record = record?;
+//!     println!("{} {:?}", record.ident, record.plate);
+//! }
+//! # Ok::<(), autoplate::Error>(())
+//! ```
+
+pub mod archive;
+pub mod cache;
+pub mod config;
+pub mod dates;
+pub mod equipment;
+pub mod error;
+pub mod export;
+pub mod ftp;
+pub mod fuel;
+pub mod inspection;
+pub mod model;
+pub mod parser;
+pub mod plate;
+pub mod quality;
+pub mod store;
+pub mod vin;
+
+pub use error::{Error, Result};
+pub use model::VehicleRecord;
@@ -1,288 +1,544 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek, Write};
-use std::path::Path;
-use std::time::SystemTime;
-
-use chrono::{DateTime, Utc};
-use suppaftp::FtpStream;
-use suppaftp::types::FileType;
-use quick_xml::events::Event;
-use quick_xml::Reader;
-use tempfile::NamedTempFile;
-use zip::ZipArchive;
-
-// LicensePlate represents a license plate record
-#[derive(Debug, Clone)]
-struct LicensePlate {
-    plate: String,
-    timestamp: SystemTime,
-}
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-// Vehicle represents the XML structure
-#[derive(Debug, Default)]
-struct Vehicle {
-    license_plate: String,
-}
+use chrono::Local;
 
-// ProgressReader wraps a reader and reports progress
-struct ProgressReader<R: Read> {
-    reader: R,
-    total: u64,
-    current: u64,
-    last_print: u64,
-}
+mod cli;
+mod display;
 
-impl<R: Read> ProgressReader<R> {
-    fn new(reader: R, total: u64) -> Self {
-        Self {
-            reader,
-            total,
-            current: 0,
-            last_print: 0,
-        }
+use autoplate::archive::{process_zip_file, Records, DEFAULT_ENTRY_PATTERN};
+use autoplate::cache::{prune, DEFAULT_CACHE_DIR};
+use autoplate::config::Config;
+use autoplate::equipment::{Catalogue, Distribution, TypeUsage};
+use autoplate::export::{
+    select_columns, CsvWriter, Format, JsonWriter, ParquetWriter, RecordWriter,
+};
+use autoplate::ftp::download_from_ftp;
+use autoplate::inspection::overdue;
+use autoplate::model;
+use autoplate::parser::Recovery;
+use autoplate::quality::{check_record, FindingsWriter, FINDINGS_FILE};
+use autoplate::store::{Store, DEFAULT_STORE_DIR};
+use autoplate::{Error, Result, VehicleRecord};
+use cli::{parse_args, Command, ConfigArgs, EquipmentView, Filter, LookupKey};
+use display::{
+    overdue_line, print_catalogue, print_distribution, print_drivetrains, print_overdue_header,
+    print_vehicle, print_vehicle_line,
+};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
     }
 }
 
-impl<R: Read> Read for ProgressReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let n = self.reader.read(buf)?;
-        self.current += n as u64;
-
-        // Print progress every 1%
-        if self.total > 0 {
-            let percent_done = (self.current * 100) / self.total;
-            if percent_done > self.last_print {
-                self.last_print = percent_done;
-                print!(
-                    "\rDownloading: {}% ({} / {} bytes)",
-                    percent_done, self.current, self.total
-                );
-                io::stdout().flush().ok();
+fn run() -> Result<()> {
+    // Check for command line arguments
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = parse_args(&args).map_err(Error::Usage)?;
+
+    let store_dir = env::var("AUTOPLATE_DB").unwrap_or_else(|_| DEFAULT_STORE_DIR.to_string());
+
+    match command {
+        Command::Import {
+            file,
+            incremental,
+            entries,
+            recovery,
+            config,
+        } => {
+            let store = if let Some(filename) = file {
+                // Use local file provided as argument
+                println!("Using local file: {}", filename);
+                let path = Path::new(&filename);
+                let source = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| filename.clone());
+                ensure_exists(path)?;
+                import_archive(path, &entries, recovery, &source, &store_dir, incremental)?
+            } else {
+                // Download from FTP server
+                let config = load_config(&config)?;
+                let (source, path) = fetch(&config, &store_dir)?;
+                import_archive(&path, &entries, recovery, &source, &store_dir, incremental)?
+            };
+
+            // Display results
+            display_results(&store)?;
+        }
+        Command::Fetch { config } => {
+            let config = load_config(&config)?;
+            let (_, path) = fetch(&config, &store_dir)?;
+            println!("✓ Archive ready: {}", path.display());
+        }
+        Command::Lookup {
+            key,
+            file,
+            recovery,
+            filter,
+            config,
+        } => {
+            let filter = view_filter(filter, &config)?;
+            let mut matches = Vec::new();
+            let mut catalogue = Catalogue::new();
+            if let Some(filename) = file {
+                let path = Path::new(&filename);
+                ensure_exists(path)?;
+                process_zip_file(
+                    opener(path),
+                    DEFAULT_ENTRY_PATTERN,
+                    recovery,
+                    &mut |record, types| {
+                        if key.matches(&record) {
+                            catalogue.adopt(&record, types);
+                            matches.push(record);
+                        }
+                        Ok(())
+                    },
+                )?;
+            } else {
+                let store = open_store(&store_dir)?;
+                matches = match &key {
+                    LookupKey::Plate(plate) => store.find_by_plate(plate)?,
+                    LookupKey::Vin(vin) => store.find_by_vin(vin)?,
+                };
+                catalogue = store.catalogue().clone();
+            }
+
+            if matches.is_empty() {
+                return Err(Error::NotFound(format!("No vehicle found with {}", key)));
+            }
+            matches.retain(|record| filter.matches(record));
+            if matches.is_empty() {
+                return Err(Error::NotFound(format!(
+                    "No vehicle with {} has the status asked for; \
+                     use --status=all to show every status",
+                    key
+                )));
+            }
+            // Show the current holder of a reissued plate first
+            matches.sort_by_key(|record| std::cmp::Reverse(record.registration_status_date));
+            for record in &matches {
+                print_vehicle(record, &catalogue);
             }
         }
+        Command::Export {
+            format,
+            columns,
+            output,
+            file,
+            entries,
+            recovery,
+            filter,
+            config,
+        } => {
+            let filter = view_filter(filter, &config)?;
+            if columns.is_some() && matches!(format, Format::Json | Format::Ndjson) {
+                return Err(Error::Usage(
+                    "--columns only applies to csv and parquet".to_string(),
+                ));
+            }
+            let columns = select_columns(columns.as_deref()).map_err(Error::Usage)?;
+            let out: Box<dyn Write> = match &output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            let mut writer: Box<dyn RecordWriter> = match format {
+                Format::Csv => Box::new(CsvWriter::new(out, columns)?),
+                Format::Json => Box::new(JsonWriter::new(out, false)?),
+                Format::Ndjson => Box::new(JsonWriter::new(out, true)?),
+                Format::Parquet => Box::new(ParquetWriter::new(out, columns)?),
+            };
 
-        Ok(n)
-    }
-}
+            let mut count = 0;
+            for_each_record(
+                file,
+                &entries,
+                recovery,
+                &store_dir,
+                &filter,
+                |record, types| {
+                    writer.write(&record, types)?;
+                    count += 1;
+                    Ok(())
+                },
+            )?;
+            writer.finish()?;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Check for command line argument
-    let args: Vec<String> = env::args().collect();
-    
-    // Initialize database (using HashMap as in-memory storage)
-    let mut db: HashMap<String, LicensePlate> = HashMap::new();
-    
-    if args.len() > 1 {
-        // Use local file provided as argument
-        let filename = &args[1];
-        println!("Using local file: {}", filename);
-        
-        let path = Path::new(filename);
-        if !path.exists() {
-            return Err(format!("File not found: {}", filename).into());
+            if let Some(path) = output {
+                println!("✓ Exported {} vehicles to {}", count, path);
+            }
+        }
+        Command::Equipment {
+            view,
+            file,
+            entries,
+            recovery,
+            filter,
+            config,
+        } => {
+            let filter = view_filter(filter, &config)?;
+            match view {
+                EquipmentView::Catalogue => {
+                    let mut usage = TypeUsage::new();
+                    let catalogue = for_each_record(
+                        file,
+                        &entries,
+                        recovery,
+                        &store_dir,
+                        &filter,
+                        |record, _| {
+                            usage.add(&record);
+                            Ok(())
+                        },
+                    )?;
+                    print_catalogue(&catalogue, &usage);
+                }
+                EquipmentView::With(query) => {
+                    let mut count = 0;
+                    for_each_record(
+                        file,
+                        &entries,
+                        recovery,
+                        &store_dir,
+                        &filter,
+                        |record, types| {
+                            let fitted = query.count(&record, types);
+                            if fitted > 0 {
+                                print_vehicle_line(&record, fitted);
+                                count += 1;
+                            }
+                            Ok(())
+                        },
+                    )?;
+                    if count == 0 {
+                        return Err(Error::NotFound(format!("No vehicle with {}", query)));
+                    }
+                    println!("{} vehicles with {}", count, query);
+                }
+                EquipmentView::Distribution(query) => {
+                    let mut distribution = Distribution::new(query.clone());
+                    let catalogue = for_each_record(
+                        file,
+                        &entries,
+                        recovery,
+                        &store_dir,
+                        &filter,
+                        |record, types| {
+                            distribution.add(&record, types);
+                            Ok(())
+                        },
+                    )?;
+                    let types: Vec<_> = catalogue.find(&query).collect();
+                    if types.is_empty() {
+                        return Err(Error::NotFound(format!("No vehicle with {}", query)));
+                    }
+                    print_distribution(&distribution, &types);
+                }
+            }
+        }
+        Command::Drivetrains {
+            file,
+            entries,
+            recovery,
+            filter,
+            config,
+        } => {
+            let filter = view_filter(filter, &config)?;
+            let mut counts = BTreeMap::new();
+            for_each_record(
+                file,
+                &entries,
+                recovery,
+                &store_dir,
+                &filter,
+                |record, _| {
+                    *counts.entry(record.details.motor.drivetrain).or_insert(0) += 1;
+                    Ok(())
+                },
+            )?;
+            print_drivetrains(&counts);
+        }
+        Command::Overdue {
+            as_of,
+            file,
+            entries,
+            recovery,
+            mut filter,
+            config,
+        } => {
+            let today = as_of.unwrap_or_else(|| Local::now().date_naive());
+            // Only registered vehicles are due for inspection, unless
+            // --status asks for others
+            filter
+                .statuses
+                .get_or_insert_with(|| vec![model::Status::Registered]);
+            let filter = view_filter(filter, &config)?;
+            let mut lines = Vec::new();
+            for_each_record(
+                file,
+                &entries,
+                recovery,
+                &store_dir,
+                &filter,
+                |record, _| {
+                    if let Some(due) = overdue(&record, today) {
+                        lines.push((due, record.ident, overdue_line(&record, due, today)));
+                    }
+                    Ok(())
+                },
+            )?;
+
+            // Longest overdue first
+            lines.sort();
+            if lines.is_empty() {
+                println!("No vehicle is overdue for inspection on {}", today);
+            } else {
+                print_overdue_header();
+                for (_, _, line) in &lines {
+                    println!("{}", line);
+                }
+                println!(
+                    "{} vehicles overdue for inspection on {}",
+                    lines.len(),
+                    today
+                );
+            }
         }
-        
-        let mut file = File::open(path)?;
-        process_zip_file(&mut file, &mut db)?;
-    } else {
-        // Download from FTP server
-        let mut temp_file = download_from_ftp()?;
-        
-        // Reset file pointer to beginning
-        temp_file.seek(io::SeekFrom::Start(0))?;
-        
-        process_zip_file(&mut temp_file, &mut db)?;
     }
 
-    // Display results
-    display_results(&db);
+    Ok(())
+}
 
+fn ensure_exists(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("File not found: {}", path.display()),
+        ));
+    }
     Ok(())
 }
 
-fn download_from_ftp() -> Result<NamedTempFile, Box<dyn std::error::Error>> {
-    // Connect to FTP server
-    println!("Connecting to FTP server...");
-    let mut ftp_stream = FtpStream::connect("5.44.137.84:21")?;
-    ftp_stream.login("anonymous", "anonymous")?;
-    
-    // Set binary transfer mode
-    ftp_stream.transfer_type(FileType::Binary)?;
-
-    // Change to target directory
-    ftp_stream.cwd("/ESStatistikListeModtag")?;
-
-    // Find newest zip file
-    let entries = ftp_stream.list(None)?;
-    
-    let mut newest_zip: Option<(String, SystemTime, u64)> = None;
-    
-    for entry_line in &entries {
-        // Parse FTP LIST output (simplified parsing)
-        let parts: Vec<&str> = entry_line.split_whitespace().collect();
-        if parts.len() < 9 {
-            continue;
+/// Run `f` on every vehicle passing `filter` in the archive `file`, or in
+/// the store when no file is given, along with the equipment types known
+/// so far. Returns every equipment type of the archive or store.
+fn for_each_record(
+    file: Option<String>,
+    entries: &str,
+    recovery: Recovery,
+    store_dir: &str,
+    filter: &Filter,
+    mut f: impl FnMut(VehicleRecord, &Catalogue) -> Result<()>,
+) -> Result<Catalogue> {
+    let mut visit = |record: VehicleRecord, catalogue: &Catalogue| {
+        if filter.matches(&record) {
+            f(record, catalogue)
+        } else {
+            Ok(())
         }
-        
-        let filename = parts[8..].join(" ");
-        if !filename.ends_with(".zip") {
-            continue;
+    };
+    if let Some(filename) = file {
+        let path = Path::new(&filename);
+        ensure_exists(path)?;
+        let mut records = Records::open(opener(path), entries, recovery)?;
+        while let Some(record) = records.next() {
+            visit(record?, records.catalogue())?;
         }
-        
-        // Extract size (5th column in LIST format)
-        let size: u64 = parts[4].parse().unwrap_or(0);
-        
-        // For simplicity, we'll use current time as modification time
-        // In production, you'd parse the date from parts[5], parts[6], parts[7]
-        let mod_time = SystemTime::now();
-        
-        if let Some((_, existing_time, _)) = newest_zip {
-            if mod_time > existing_time {
-                newest_zip = Some((filename, mod_time, size));
-            }
-        } else {
-            newest_zip = Some((filename, mod_time, size));
+        Ok(records.catalogue().clone())
+    } else {
+        let store = open_store(store_dir)?;
+        for record in store.records() {
+            visit(record?, store.catalogue())?;
         }
+        Ok(store.catalogue().clone())
     }
+}
 
-    let (zip_name, zip_time, zip_size) = newest_zip
-        .ok_or("No zip files found in directory")?;
-
-    let dt: DateTime<Utc> = zip_time.into();
-    println!("Downloading: {} ({})", zip_name, dt.to_rfc3339());
-    println!("File size: {:.2} MB", zip_size as f64 / (1024.0 * 1024.0));
-
-    // Download zip file to temporary file
-    let mut temp_file = NamedTempFile::new()?;
-    
-    // Get a reader for the remote file
-    let reader = ftp_stream.retr_as_stream(&zip_name)?;
-    
-    // Create progress reader
-    let mut progress_reader = ProgressReader::new(reader, zip_size);
-    
-    // Stream download to temp file with progress
-    let written = io::copy(&mut progress_reader, &mut temp_file)?;
-    
-    println!("\n✓ Downloaded {} bytes", written);
-    
-    // Finalize the transfer
-    ftp_stream.finalize_retr_stream(progress_reader.reader)?;
-
-    // Quit FTP connection
-    let _ = ftp_stream.quit();
-    
-    Ok(temp_file)
+/// Opens `path` once for every thread reading the archive
+fn opener(path: &Path) -> impl Fn() -> io::Result<File> + Send + Sync + 'static {
+    let path = path.to_path_buf();
+    move || File::open(&path)
 }
 
-fn process_zip_file<R: Read + Seek>(
-    file: &mut R,
-    db: &mut HashMap<String, LicensePlate>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut archive = ZipArchive::new(file)?;
+/// Open the store for reading, telling a missing store apart from other
+/// I/O errors
+fn open_store(store_dir: &str) -> Result<Store> {
+    Store::open(store_dir).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::NotFound(e.to_string()),
+        _ => e.into(),
+    })
+}
 
-    let mut processed_count = 0;
+fn load_config(args: &ConfigArgs) -> Result<Config> {
+    Config::load(args.file.as_deref(), &args.overrides).map_err(Error::Config)
+}
 
-    for i in 0..archive.len() {
-        let zip_file = archive.by_index(i)?;
-        
-        if zip_file.is_dir() {
-            continue;
-        }
+/// Without `--status`, only registered vehicles are listed when the
+/// `view.hide-inactive` setting is on
+fn view_filter(mut filter: Filter, config: &ConfigArgs) -> Result<Filter> {
+    if filter.statuses.is_none() && load_config(config)?.view.hide_inactive {
+        filter.statuses = Some(vec![model::Status::Registered]);
+    }
+    Ok(filter)
+}
+
+/// Make sure the newest remote archive is in the download cache, then
+/// apply the retention setting. Returns the archive's name and local path.
+fn fetch(config: &Config, store_dir: &str) -> Result<(String, PathBuf)> {
+    let cache_dir = match &config.cache.directory {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(store_dir).join(DEFAULT_CACHE_DIR),
+    };
+
+    let (name, path) = download_from_ftp(&config.ftp, &cache_dir)?;
+
+    for removed in prune(&cache_dir, &config.ftp.pattern, config.cache.keep, &name)? {
+        println!("Removed old download {}", removed.display());
+    }
+
+    Ok((name, path))
+}
+
+/// Load an archive into the store, either replacing its contents or, when
+/// `incremental` is set, applying only the differences
+fn import_archive(
+    archive: &Path,
+    entries: &str,
+    recovery: Recovery,
+    source: &str,
+    store_dir: &str,
+    incremental: bool,
+) -> Result<Store> {
+    let findings_path = Path::new(store_dir).join(FINDINGS_FILE);
 
+    let store = if incremental {
+        let mut store = Store::open_for_update(store_dir)?;
+        let mut update = store.update(source)?;
+        let mut findings = FindingsWriter::create(&findings_path)?;
+        let parsed = process_zip_file(opener(archive), entries, recovery, &mut |record, types| {
+            findings.write(check_record(&record))?;
+            update.apply(&record, types)
+        })?;
+        let summary = update.finish(parsed)?;
         println!(
-            "Processing: {} ({:.2} KB)",
-            zip_file.name(),
-            zip_file.size() as f64 / 1024.0
+            "✓ Updated {}: {} added, {} changed, {} removed, {} unchanged",
+            store_dir, summary.added, summary.changed, summary.removed, summary.unchanged
         );
+        if summary.removal_skipped {
+            println!(
+                "⚠ Vehicles missing from the dump were kept because damaged records were skipped"
+            );
+        }
+        report_findings(findings)?;
+        store
+    } else {
+        let mut writer = Store::rebuild(store_dir)?;
+        let mut findings = FindingsWriter::create(&findings_path)?;
+        process_zip_file(opener(archive), entries, recovery, &mut |record, types| {
+            findings.write(check_record(&record))?;
+            writer.insert(&record, types)
+        })?;
+        let store = writer.finish()?;
+        println!("✓ Saved {} vehicles to {}", store.len(), store_dir);
+        report_findings(findings)?;
+        store
+    };
 
-        // Stream parse XML directly from zip without loading into memory
-        let mut reader = Reader::from_reader(BufReader::new(zip_file));
-        reader.trim_text(true);
-
-        let mut buf = Vec::new();
-        let mut in_vehicle = false;
-        let mut current_vehicle = Vehicle::default();
-        let mut in_license_plate = false;
-
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(ref e)) => {
-                    match e.name().as_ref() {
-                        b"Vehicle" => {
-                            in_vehicle = true;
-                            current_vehicle = Vehicle::default();
-                        }
-                        b"LicensePlate" => {
-                            in_license_plate = true;
-                        }
-                        _ => {}
-                    }
-                }
-                Ok(Event::Text(e)) => {
-                    if in_vehicle && in_license_plate {
-                        if let Ok(text) = e.unescape() {
-                            current_vehicle.license_plate = text.into_owned();
-                        }
-                    }
-                }
-                Ok(Event::End(ref e)) => {
-                    match e.name().as_ref() {
-                        b"LicensePlate" => {
-                            in_license_plate = false;
-                        }
-                        b"Vehicle" => {
-                            in_vehicle = false;
-                            
-                            if !current_vehicle.license_plate.is_empty() {
-                                let plate = LicensePlate {
-                                    plate: current_vehicle.license_plate.clone(),
-                                    timestamp: SystemTime::now(),
-                                };
-                                db.insert(plate.plate.clone(), plate);
-                                processed_count += 1;
-
-                                // Progress indicator
-                                if processed_count % 1000 == 0 {
-                                    println!("  Processed {} plates...", processed_count);
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                Ok(Event::Eof) => break,
-                Err(e) => {
-                    eprintln!(
-                        "Warning: XML parse error at position {}: {}",
-                        reader.buffer_position(),
-                        e
-                    );
-                    break;
-                }
-                _ => {}
-            }
-            buf.clear();
+    Ok(store)
+}
+
+/// Put the findings report in place and show the first few findings
+fn report_findings(mut findings: FindingsWriter) -> io::Result<()> {
+    findings.finish()?;
+    if findings.count() > 0 {
+        println!(
+            "⚠ {} data-quality findings, see {}",
+            findings.count(),
+            findings.path().display()
+        );
+        for finding in findings.sample() {
+            println!("  {}", finding);
         }
     }
-
-    println!("\n✓ Successfully processed {} license plates", processed_count);
     Ok(())
 }
 
-fn display_results(db: &HashMap<String, LicensePlate>) {
-    let mut plates: Vec<String> = db.keys().cloned().collect();
+fn display_results(store: &Store) -> io::Result<()> {
+    let mut plates: Vec<&str> = store.plates().collect();
     plates.sort();
 
-    println!("\n=== License Plates in Database ({} total) ===", plates.len());
-    
+    println!(
+        "\n=== License Plates in Database ({} total, {} VINs) ===",
+        plates.len(),
+        store.vin_count()
+    );
+
     for (i, plate) in plates.iter().enumerate() {
-        println!("{}. {}", i + 1, plate);
+        let make_model = store
+            .find_by_plate(plate)?
+            .first()
+            .map(|record| {
+                let designation = &record.details.designation;
+                let name = |coded: &Option<model::Coded>| {
+                    coded
+                        .as_ref()
+                        .and_then(|c| c.name.clone())
+                        .unwrap_or_default()
+                };
+                format!("{} {}", name(&designation.make), name(&designation.model))
+            })
+            .unwrap_or_default();
+        println!("{}. {} {}", i + 1, plate, make_model.trim());
         if i >= 9 {
-            println!("... and {} more", plates.len() - 10);
+            if plates.len() > 10 {
+                println!("... and {} more", plates.len() - 10);
+            }
             break;
         }
     }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hide_inactive_is_the_default_status_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("autoplate.conf");
+        std::fs::write(&file, "[view]\nhide-inactive = true\n").unwrap();
+        let config = |overrides: &[(&str, &str)]| ConfigArgs {
+            file: Some(file.to_str().unwrap().to_string()),
+            overrides: overrides
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        let registered = Some(vec![model::Status::Registered]);
+
+        let filter = view_filter(Filter::default(), &config(&[])).unwrap();
+        assert_eq!(filter.statuses, registered);
+
+        // --status wins over the setting, even when it asks for every status
+        for statuses in [vec![], vec![model::Status::Scrapped]] {
+            let asked = Filter {
+                statuses: Some(statuses),
+                ..Filter::default()
+            };
+            assert_eq!(view_filter(asked.clone(), &config(&[])).unwrap(), asked);
+        }
+
+        let shown = config(&[("view.hide-inactive", "false")]);
+        let filter = view_filter(Filter::default(), &shown).unwrap();
+        assert_eq!(filter.statuses, None);
+    }
 }
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::fuel::{Drivetrain, FuelKind};
+use crate::plate::PlateClass;
+
+/// One ns:Statistik record from the DMR dump
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehicleRecord {
+    /// KoeretoejIdent, the permanent DMR vehicle id
+    pub ident: u64,
+    /// KoeretoejArtNummer / KoeretoejArtNavn, e.g. "Personbil"
+    pub kind: Option<Coded>,
+    /// KoeretoejAnvendelseStruktur, e.g. "Privat personkørsel"
+    pub usage: Option<Coded>,
+    /// RegistreringNummerNummer
+    pub plate: Option<String>,
+    /// Layout of the plate, derived from RegistreringNummerNummer
+    pub plate_class: Option<PlateClass>,
+    /// RegistreringNummerUdloebDato
+    pub plate_expiry: Option<NaiveDate>,
+    /// KoeretoejOplysningGrundStruktur
+    pub details: VehicleDetails,
+    /// SynResultatStruktur
+    pub inspection: Option<Inspection>,
+    /// KoeretoejRegistreringStatus
+    pub registration_status: Option<Status>,
+    /// KoeretoejRegistreringStatusDato
+    pub registration_status_date: Option<DateTime<FixedOffset>>,
+}
+
+/// A number/name pair, the shape DMR uses for every type catalogue
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Coded {
+    pub number: Option<u64>,
+    pub name: Option<String>,
+}
+
+/// KoeretoejOplysningGrundStruktur
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehicleDetails {
+    /// KoeretoejOplysningOprettetUdFra, e.g. "Typeattest"
+    pub created_from: Option<String>,
+    pub status: Option<Status>,
+    pub status_date: Option<DateTime<FixedOffset>>,
+    pub first_registration: Option<NaiveDate>,
+    /// KoeretoejOplysningStelNummer
+    pub vin: Option<String>,
+    /// KoeretoejOplysningStelNummerAnbringelse
+    pub vin_location: Option<String>,
+    pub model_year: Option<u32>,
+    pub total_weight: Option<u32>,
+    pub technical_total_weight: Option<u32>,
+    pub curb_weight_min: Option<u32>,
+    pub curb_weight_max: Option<u32>,
+    pub train_weight: Option<u32>,
+    pub axles: Option<u32>,
+    pub driving_axles: Option<u32>,
+    pub seats_min: Option<u32>,
+    pub seats_max: Option<u32>,
+    pub doors: Option<u32>,
+    pub coupling_possible: Option<bool>,
+    pub coupling_weight_unbraked: Option<u32>,
+    pub coupling_weight_braked: Option<u32>,
+    pub ncap_test: Option<bool>,
+    /// KoeretoejOplysningKoeretoejstand, e.g. "Middel"
+    pub condition: Option<String>,
+    pub taxi_suitable: Option<bool>,
+    pub traffic_damage: Option<bool>,
+    pub type_notification_number: Option<String>,
+    pub type_approval_number: Option<String>,
+    pub comment: Option<String>,
+    /// KoeretoejBetegnelseStruktur
+    pub designation: Designation,
+    /// KoeretoejFarveStruktur
+    pub colour: Option<Coded>,
+    /// KarrosseriTypeStruktur
+    pub body_type: Option<Coded>,
+    /// KoeretoejNormStruktur
+    pub norm: Option<Coded>,
+    /// KoeretoejMiljoeOplysningStruktur
+    pub environment: Environment,
+    /// KoeretoejMotorStruktur
+    pub motor: Motor,
+    /// KoeretoejUdstyrSamlingStruktur
+    pub equipment: Vec<Equipment>,
+}
+
+/// Make, model, variant and type as registered in KoeretoejBetegnelseStruktur
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Designation {
+    pub make: Option<Coded>,
+    pub model: Option<Coded>,
+    pub variant: Option<Coded>,
+    pub vehicle_type: Option<Coded>,
+}
+
+/// KoeretoejMiljoeOplysningStruktur
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Environment {
+    pub particle_filter: Option<bool>,
+    /// CO2 emission in g/km
+    pub co2_emission: Option<Decimal>,
+}
+
+/// KoeretoejMotorStruktur
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Motor {
+    pub cylinders: Option<u32>,
+    /// Displacement in cm³
+    pub displacement: Option<Decimal>,
+    pub displacement_unavailable: Option<bool>,
+    /// Maximum power in kW
+    pub max_power: Option<Decimal>,
+    pub max_power_unavailable: Option<bool>,
+    pub odometer: Option<u32>,
+    pub odometer_documented: Option<bool>,
+    pub odometer_unavailable: Option<bool>,
+    pub innovative_technology: Option<bool>,
+    /// KoeretoejDrivmiddelSamlingStruktur
+    pub fuels: Vec<Fuel>,
+    /// Derived from `fuels`
+    pub drivetrain: Option<Drivetrain>,
+}
+
+/// DrivmiddelStruktur
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fuel {
+    /// DrivkraftTypeStruktur, e.g. "Benzin" or "El"
+    pub drive_type: Coded,
+    pub km_per_liter: Option<Decimal>,
+    /// Electric consumption in Wh/km
+    pub electric_consumption: Option<Decimal>,
+    /// KoeretoejMotorDrivmiddelPrimaer
+    pub primary: Option<bool>,
+}
+
+impl Fuel {
+    pub fn kind(&self) -> FuelKind {
+        FuelKind::of(&self.drive_type)
+    }
+}
+
+/// KoeretoejUdstyrStruktur, one line of a vehicle's equipment list
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Equipment {
+    /// KoeretoejUdstyrAntal; 0 means the vehicle lacks the equipment
+    pub count: Option<u32>,
+    /// KoeretoejUdstyrTypeNummer. The rest of KoeretoejUdstyrTypeStruktur
+    /// is kept once per type, see [`crate::equipment::Catalogue`].
+    pub type_number: Option<u32>,
+}
+
+impl Equipment {
+    /// Number of items fitted, 1 when the dump gives no count
+    pub fn fitted(&self) -> u32 {
+        self.count.unwrap_or(1)
+    }
+}
+
+/// KoeretoejUdstyrTypeStruktur, an entry of the DMR equipment catalogue.
+/// The dump repeats the full entry for every vehicle; records only keep
+/// its number, see [`crate::equipment::Catalogue`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct EquipmentType {
+    /// KoeretoejUdstyrTypeNummer
+    pub number: Option<u32>,
+    /// KoeretoejUdstyrTypeNavn, e.g. "Airbags"
+    pub name: Option<String>,
+    /// KoeretoejUdstyrTypeVisesVedSyn
+    pub shown_at_inspection: Option<bool>,
+    /// KoeretoejUdstyrTypeVisesVedForespoergsel
+    pub shown_on_inquiry: Option<bool>,
+    /// KoeretoejUdstyrTypeVisesVedStandardOprettelse
+    pub shown_on_standard_creation: Option<bool>,
+}
+
+/// SynResultatStruktur, the latest inspection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inspection {
+    /// SynResultatSynsType, e.g. "RegistreringssynToldsyn"
+    pub kind: Option<String>,
+    pub date: Option<NaiveDate>,
+    /// SynResultatSynsResultat, e.g. "Godkendt"
+    pub result: Option<String>,
+    pub status: Option<String>,
+    pub status_date: Option<NaiveDate>,
+}
+
+/// Registration status as used by KoeretoejRegistreringStatus and
+/// KoeretoejOplysningStatus
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Status {
+    Registered,
+    Deregistered,
+    Exported,
+    Scrapped,
+    Other(String),
+}
+
+impl Status {
+    /// The spelling used in the dump
+    pub fn as_dmr(&self) -> &str {
+        match self {
+            Status::Registered => "Registreret",
+            Status::Deregistered => "Afmeldt",
+            Status::Exported => "Eksporteret",
+            Status::Scrapped => "Skrotet",
+            Status::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for Status {
+    fn from(s: &str) -> Self {
+        match s {
+            "Registreret" => Status::Registered,
+            "Afmeldt" => Status::Deregistered,
+            "Eksporteret" => Status::Exported,
+            "Skrotet" => Status::Scrapped,
+            other => Status::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_dmr())
+    }
+}
+
+/// Exact decimal number as written in the dump, e.g. `2773.0` or `11.4`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i64,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i64, scale: u32) -> Self {
+        Decimal { mantissa, scale }
+    }
+
+    pub fn mantissa(self) -> i64 {
+        self.mantissa
+    }
+
+    /// Number of digits after the decimal point
+    pub fn scale(self) -> u32 {
+        self.scale
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err("empty number".to_string());
+        }
+        if !int_part
+            .chars()
+            .chain(frac_part.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return Err("not a decimal number".to_string());
+        }
+
+        let digits = format!("{}{}", int_part, frac_part);
+        let mut mantissa: i64 = digits
+            .parse()
+            .map_err(|_| "decimal out of range".to_string())?;
+        if negative {
+            mantissa = -mantissa;
+        }
+        Ok(Decimal {
+            mantissa,
+            scale: frac_part.len() as u32,
+        })
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.mantissa);
+        }
+        let digits = format!(
+            "{:0>width$}",
+            self.mantissa.unsigned_abs(),
+            width = self.scale as usize + 1
+        );
+        let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
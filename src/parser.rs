@@ -0,0 +1,917 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::str::FromStr;
+
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+
+use crate::dates::{parse_date, parse_datetime};
+use crate::equipment::Catalogue;
+use crate::error::{Error, Result};
+use crate::fuel;
+use crate::model::{Coded, Equipment, EquipmentType, Fuel, Inspection, Status, VehicleRecord};
+use crate::{plate, vin};
+
+/// Namespace used by every element in the SKAT DMR statistics dump
+pub const DMR_NAMESPACE: &[u8] = b"http://skat.dk/dmr/2007/05/31/";
+
+/// A leaf element whose text could not be converted to its typed field
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    pub element: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} value {:?}: {}",
+            self.element, self.value, self.message
+        )
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+/// What the parser does with a record it cannot read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Recovery {
+    /// Warn, drop the record and carry on with the next ns:Statistik
+    #[default]
+    Skip,
+    /// Stop at the first problem, including field values that do not fit
+    /// their type
+    Strict,
+}
+
+/// Outcome of parsing one document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseSummary {
+    /// Records passed to `on_record`
+    pub records: usize,
+    /// Records dropped because they were malformed or incomplete
+    pub skipped: usize,
+}
+
+/// Root element of the made-up prologue a resynchronised reader starts on
+const RESYNC_ROOT: &str = "autoplate-resync";
+
+/// Stream parse a DMR XML document, calling `on_record` for every
+/// `ns:Statistik` element with the equipment types known so far. See
+/// [`XmlRecords`]; an error from `on_record` also stops the parse.
+pub fn parse_statistik<R: BufRead>(
+    source: R,
+    recovery: Recovery,
+    mut on_record: impl FnMut(VehicleRecord, &Catalogue) -> io::Result<()>,
+) -> Result<ParseSummary> {
+    let mut records = XmlRecords::new(source, recovery);
+    while let Some(record) = records.next() {
+        on_record(record?, records.catalogue())?;
+    }
+    Ok(records.summary())
+}
+
+/// The `ns:Statistik` records of a DMR XML document, parsed as they are
+/// pulled. Memory use does not depend on the size of the document.
+///
+/// With [`Recovery::Skip`], malformed XML drops the record it is in and
+/// parsing resumes at the next `ns:Statistik` start tag; records without
+/// KoeretoejIdent are dropped too, and field values that do not fit their
+/// type are reported as warnings and left empty. With [`Recovery::Strict`]
+/// each of these is returned as an error. Iteration ends after the first
+/// error returned, and failing to read the input is always returned.
+///
+/// Elements are matched on their resolved namespace, so the prefix used in
+/// the document does not matter. Equipment types go to [`catalogue`]
+/// and records keep their number.
+///
+/// [`catalogue`]: XmlRecords::catalogue
+pub struct XmlRecords<R> {
+    /// Replaced after a syntax error, and only `None` while that happens
+    reader: Option<NsReader<Resync<R>>>,
+    origin: Origin,
+    recovery: Recovery,
+    buf: Vec<u8>,
+    /// Local names of the open DMR elements below the current ns:Statistik
+    path: Vec<Vec<u8>>,
+    current: Option<VehicleRecord>,
+    /// KoeretoejUdstyrTypeStruktur of each equipment of `current`
+    equipment_types: Vec<EquipmentType>,
+    catalogue: Catalogue,
+    text: String,
+    /// ns:Statistik as written in the document, which is what
+    /// resynchronising searches for
+    statistik: Option<Vec<u8>>,
+    summary: ParseSummary,
+    finished: bool,
+}
+
+impl<R: Read> XmlRecords<BufReader<R>> {
+    /// Parse an unbuffered source
+    pub fn from_read(source: R, recovery: Recovery) -> Self {
+        XmlRecords::new(BufReader::new(source), recovery)
+    }
+}
+
+impl<R: BufRead> XmlRecords<R> {
+    pub fn new(source: R, recovery: Recovery) -> Self {
+        XmlRecords {
+            reader: Some(new_reader(Resync::new(source))),
+            origin: Origin::default(),
+            recovery,
+            buf: Vec::new(),
+            path: Vec::new(),
+            current: None,
+            equipment_types: Vec::new(),
+            catalogue: Catalogue::new(),
+            text: String::new(),
+            statistik: None,
+            summary: ParseSummary::default(),
+            finished: false,
+        }
+    }
+
+    /// Records returned and skipped so far
+    pub fn summary(&self) -> ParseSummary {
+        self.summary
+    }
+
+    /// Equipment types described so far, including every type a returned
+    /// record refers to
+    pub fn catalogue(&self) -> &Catalogue {
+        &self.catalogue
+    }
+
+    /// Give back the source, positioned somewhere after the last record
+    /// returned
+    pub fn into_inner(self) -> R {
+        self.reader.expect(RESYNCING).into_inner().inner
+    }
+
+    /// Handle one XML event, returning the record it completes
+    fn step(&mut self) -> Result<Option<VehicleRecord>> {
+        let reader = self.reader.as_mut().expect(RESYNCING);
+        let origin = &self.origin;
+        let current = &mut self.current;
+        let equipment_types = &mut self.equipment_types;
+        let path = &mut self.path;
+        let text = &mut self.text;
+
+        let record = match reader.read_resolved_event_into(&mut self.buf) {
+            Ok((ns, Event::Start(e))) if is_dmr(&ns) => {
+                let local = e.local_name().as_ref().to_vec();
+                if let Some(record) = current.as_mut() {
+                    open(record, equipment_types, &local);
+                    path.push(local);
+                } else if local == b"Statistik" {
+                    self.statistik
+                        .get_or_insert_with(|| e.name().as_ref().to_vec());
+                    *current = Some(VehicleRecord::default());
+                    equipment_types.clear();
+                }
+                text.clear();
+                None
+            }
+            Ok((_, Event::Text(e))) if current.is_some() => match e.unescape() {
+                Ok(unescaped) => {
+                    text.push_str(&unescaped);
+                    None
+                }
+                Err(source) => return Err(xml_error(source, origin.at(reader))),
+            },
+            Ok((_, Event::End(e))) => {
+                let mut complete = None;
+                let (ns, local) = reader.resolve_element(e.name());
+                if let (true, Some(record)) = (is_dmr(&ns), current.as_mut()) {
+                    if path.pop().is_some() {
+                        if !text.is_empty() {
+                            if let Err(source) =
+                                assign(record, equipment_types, local.as_ref(), text)
+                            {
+                                let error = Error::Validation {
+                                    entry: None,
+                                    position: origin.at(reader),
+                                    ident: record.ident,
+                                    source,
+                                };
+                                match self.recovery {
+                                    Recovery::Skip => eprintln!("Warning: {}", error),
+                                    Recovery::Strict => return Err(error),
+                                }
+                            }
+                        }
+                    } else if local.as_ref() == b"Statistik" {
+                        let mut record = current.take().unwrap_or_default();
+                        if record.ident == 0 {
+                            return Err(Error::Schema {
+                                entry: None,
+                                position: origin.at(reader),
+                                message: "ns:Statistik without KoeretoejIdent".to_string(),
+                            });
+                        }
+                        let motor = &mut record.details.motor;
+                        motor.drivetrain = fuel::classify(&motor.fuels);
+                        for (equipment, equipment_type) in record
+                            .details
+                            .equipment
+                            .iter_mut()
+                            .zip(equipment_types.drain(..))
+                        {
+                            equipment.type_number = equipment_type.number;
+                            self.catalogue.insert(equipment_type);
+                        }
+                        complete = Some(record);
+                    }
+                }
+                text.clear();
+                complete
+            }
+            Ok((_, Event::Eof)) if current.take().is_some() => {
+                return Err(Error::Schema {
+                    entry: None,
+                    position: origin.at(reader),
+                    message: "document ends inside ns:Statistik".to_string(),
+                })
+            }
+            Ok((_, Event::Eof)) => {
+                self.finished = true;
+                None
+            }
+            // The document's root closing after a resynchronisation
+            Err(quick_xml::Error::EndEventMismatch { expected, .. }) if expected == RESYNC_ROOT => {
+                self.finished = true;
+                None
+            }
+            Err(source) => return Err(xml_error(source, origin.at(reader))),
+            _ => None,
+        };
+        self.buf.clear();
+        Ok(record)
+    }
+
+    /// The reader cannot continue after a syntax error, so look for the
+    /// next record in the raw input and start a new reader there
+    fn resync(&mut self, name: &[u8]) -> io::Result<()> {
+        self.current = None;
+        self.path.clear();
+        self.text.clear();
+        self.buf.clear();
+
+        let mut input = self.reader.take().expect(RESYNCING).into_inner();
+        let found = input.skip_to(name);
+        match found {
+            Ok(Some(start)) => {
+                let prologue = resync_prologue(name);
+                self.origin = Origin {
+                    base: start,
+                    shift: prologue.len(),
+                };
+                input.replay(prologue, name);
+            }
+            Ok(None) | Err(_) => self.finished = true,
+        }
+        self.reader = Some(new_reader(input));
+        found.map(|_| ())
+    }
+
+    fn fail(&mut self, error: Error) -> Option<Result<VehicleRecord>> {
+        self.finished = true;
+        Some(Err(error))
+    }
+}
+
+impl<R: BufRead> Iterator for XmlRecords<R> {
+    type Item = Result<VehicleRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let error = match self.step() {
+                Ok(Some(record)) => {
+                    self.summary.records += 1;
+                    return Some(Ok(record));
+                }
+                Ok(None) => continue,
+                Err(error @ Error::Io(_)) => return self.fail(error),
+                Err(error) if self.recovery == Recovery::Strict => return self.fail(error),
+                Err(error) => error,
+            };
+
+            // A record-level problem: the record is already closed
+            if let Error::Schema { .. } = error {
+                eprintln!("Warning: {}; record skipped", error);
+                self.summary.skipped += 1;
+                continue;
+            }
+
+            let Some(name) = self.statistik.clone() else {
+                return self.fail(error);
+            };
+            eprintln!("Warning: {}; skipping to the next ns:Statistik", error);
+            if self.current.is_some() {
+                self.summary.skipped += 1;
+            }
+            if let Err(e) = self.resync(&name) {
+                return self.fail(e.into());
+            }
+        }
+        None
+    }
+}
+
+const RESYNCING: &str = "the reader is only taken while resynchronising";
+
+fn new_reader<R: BufRead>(input: Resync<R>) -> NsReader<Resync<R>> {
+    let mut reader = NsReader::from_reader(input);
+    reader.trim_text(true);
+    reader
+}
+
+/// Start of a document that declares the DMR namespace under the prefix
+/// used in `name`, so a record can be parsed without the real root element
+fn resync_prologue(name: &[u8]) -> Vec<u8> {
+    let mut prologue = format!("<{} xmlns", RESYNC_ROOT).into_bytes();
+    if let Some(colon) = name.iter().position(|&b| b == b':') {
+        prologue.push(b':');
+        prologue.extend_from_slice(&name[..colon]);
+    }
+    prologue.extend_from_slice(b"=\"");
+    prologue.extend_from_slice(DMR_NAMESPACE);
+    prologue.extend_from_slice(b"\">");
+    prologue
+}
+
+/// Maps a position in the current reader back to the input: the reader
+/// started `shift` bytes of prologue before input offset `base`
+#[derive(Default)]
+struct Origin {
+    base: usize,
+    shift: usize,
+}
+
+impl Origin {
+    fn at<R>(&self, reader: &NsReader<R>) -> usize {
+        self.base + reader.buffer_position().saturating_sub(self.shift)
+    }
+}
+
+/// The parser's input, with room for bytes replayed in front of the rest
+/// of it after a resynchronisation
+struct Resync<R> {
+    inner: R,
+    /// Bytes consumed from `inner`
+    consumed: usize,
+    replay: Vec<u8>,
+    replayed: usize,
+}
+
+impl<R: BufRead> Resync<R> {
+    fn new(inner: R) -> Self {
+        Resync {
+            inner,
+            consumed: 0,
+            replay: Vec::new(),
+            replayed: 0,
+        }
+    }
+
+    /// Discard input up to the next start tag called `name` and return the
+    /// offset of its `<`, leaving the rest of the tag unread. `None` at the
+    /// end of the input.
+    fn skip_to(&mut self, name: &[u8]) -> io::Result<Option<usize>> {
+        self.replay.clear();
+        self.replayed = 0;
+
+        // Bytes of `<name` matched so far
+        let mut matched = 0;
+        loop {
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                return Ok(None);
+            }
+
+            let mut used = 0;
+            let mut found = false;
+            for &byte in available {
+                if matched == name.len() + 1 {
+                    if byte == b'>' || byte == b'/' || byte.is_ascii_whitespace() {
+                        found = true;
+                        break;
+                    }
+                    matched = 0;
+                }
+                matched = match byte {
+                    b'<' => 1,
+                    _ if matched > 0 && name[matched - 1] == byte => matched + 1,
+                    _ => 0,
+                };
+                used += 1;
+            }
+
+            self.inner.consume(used);
+            self.consumed += used;
+            if found {
+                return Ok(Some(self.consumed - name.len() - 1));
+            }
+        }
+    }
+
+    /// Read `prologue` and `<name` before the rest of the input
+    fn replay(&mut self, prologue: Vec<u8>, name: &[u8]) {
+        self.replay = prologue;
+        self.replay.push(b'<');
+        self.replay.extend_from_slice(name);
+        self.replayed = 0;
+    }
+}
+
+impl<R: BufRead> Read for Resync<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Resync<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.replayed < self.replay.len() {
+            return Ok(&self.replay[self.replayed..]);
+        }
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.replayed < self.replay.len() {
+            self.replayed += amt;
+        } else {
+            self.inner.consume(amt);
+            self.consumed += amt;
+        }
+    }
+}
+
+/// Failing to read the input is an I/O error, not an XML one
+fn xml_error(source: quick_xml::Error, position: usize) -> Error {
+    match source {
+        quick_xml::Error::Io(e) => Error::Io(io::Error::new(e.kind(), e.to_string())),
+        source => Error::Xml {
+            entry: None,
+            position,
+            source,
+        },
+    }
+}
+
+fn is_dmr(ns: &ResolveResult) -> bool {
+    matches!(ns, ResolveResult::Bound(Namespace(uri)) if *uri == DMR_NAMESPACE)
+}
+
+/// Start a new entry for elements that repeat within a record
+fn open(record: &mut VehicleRecord, equipment_types: &mut Vec<EquipmentType>, element: &[u8]) {
+    match element {
+        b"DrivmiddelStruktur" => record.details.motor.fuels.push(Fuel::default()),
+        b"KoeretoejUdstyrStruktur" => {
+            record.details.equipment.push(Equipment::default());
+            equipment_types.push(EquipmentType::default());
+        }
+        b"SynResultatStruktur" => record.inspection = Some(Inspection::default()),
+        _ => {}
+    }
+}
+
+/// Store the text of a leaf element in its typed field. Leaf names are
+/// unique across the Statistik structure, so the local name is enough to
+/// route the value. Equipment types go to `equipment_types`, one per entry
+/// of the record's equipment list.
+fn assign(
+    record: &mut VehicleRecord,
+    equipment_types: &mut Vec<EquipmentType>,
+    element: &[u8],
+    text: &str,
+) -> Result<(), FieldError> {
+    let details = &mut record.details;
+    let designation = &mut details.designation;
+    let environment = &mut details.environment;
+    let motor = &mut details.motor;
+    let equipment = &mut details.equipment;
+
+    let result = match element {
+        b"KoeretoejIdent" => number(text).map(|v| record.ident = v),
+        b"KoeretoejArtNummer" => number(text).map(|v| coded(&mut record.kind).number = Some(v)),
+        b"KoeretoejArtNavn" => string(text).map(|v| coded(&mut record.kind).name = Some(v)),
+        b"KoeretoejAnvendelseNummer" => {
+            number(text).map(|v| coded(&mut record.usage).number = Some(v))
+        }
+        b"KoeretoejAnvendelseNavn" => string(text).map(|v| coded(&mut record.usage).name = Some(v)),
+        b"RegistreringNummerNummer" => plate::parse(text)
+            .map(|(plate, class)| {
+                record.plate = Some(plate);
+                record.plate_class = Some(class);
+            })
+            .map_err(|e| e.to_string()),
+        b"RegistreringNummerUdloebDato" => date(text).map(|v| record.plate_expiry = Some(v)),
+        b"KoeretoejRegistreringStatus" => {
+            string(text).map(|v| record.registration_status = Some(Status::from(v.as_str())))
+        }
+        b"KoeretoejRegistreringStatusDato" => {
+            datetime(text).map(|v| record.registration_status_date = Some(v))
+        }
+
+        b"KoeretoejOplysningOprettetUdFra" => string(text).map(|v| details.created_from = Some(v)),
+        b"KoeretoejOplysningStatus" => {
+            string(text).map(|v| details.status = Some(Status::from(v.as_str())))
+        }
+        b"KoeretoejOplysningStatusDato" => datetime(text).map(|v| details.status_date = Some(v)),
+        b"KoeretoejOplysningFoersteRegistreringDato" => {
+            date(text).map(|v| details.first_registration = Some(v))
+        }
+        b"KoeretoejOplysningStelNummer" => {
+            string(text).map(|v| details.vin = Some(vin::normalize(&v)))
+        }
+        b"KoeretoejOplysningStelNummerAnbringelse" => {
+            string(text).map(|v| details.vin_location = Some(v))
+        }
+        b"KoeretoejOplysningModelAar" => number(text).map(|v| details.model_year = Some(v)),
+        b"KoeretoejOplysningTotalVaegt" => number(text).map(|v| details.total_weight = Some(v)),
+        b"KoeretoejOplysningTekniskTotalVaegt" => {
+            number(text).map(|v| details.technical_total_weight = Some(v))
+        }
+        b"KoeretoejOplysningKoereklarVaegtMinimum" => {
+            number(text).map(|v| details.curb_weight_min = Some(v))
+        }
+        b"KoeretoejOplysningKoereklarVaegtMaksimum" => {
+            number(text).map(|v| details.curb_weight_max = Some(v))
+        }
+        b"KoeretoejOplysningVogntogVaegt" => number(text).map(|v| details.train_weight = Some(v)),
+        b"KoeretoejOplysningAkselAntal" => number(text).map(|v| details.axles = Some(v)),
+        b"KoeretoejOplysningTraekkendeAksler" => {
+            number(text).map(|v| details.driving_axles = Some(v))
+        }
+        b"KoeretoejOplysningSiddepladserMinimum" => {
+            number(text).map(|v| details.seats_min = Some(v))
+        }
+        b"KoeretoejOplysningSiddepladserMaksimum" => {
+            number(text).map(|v| details.seats_max = Some(v))
+        }
+        b"KoeretoejOplysningAntalDoere" => number(text).map(|v| details.doors = Some(v)),
+        b"KoeretoejOplysningTilkoblingMulighed" => {
+            boolean(text).map(|v| details.coupling_possible = Some(v))
+        }
+        b"KoeretoejOplysningTilkoblingsvaegtUdenBremser" => {
+            number(text).map(|v| details.coupling_weight_unbraked = Some(v))
+        }
+        b"KoeretoejOplysningTilkoblingsvaegtMedBremser" => {
+            number(text).map(|v| details.coupling_weight_braked = Some(v))
+        }
+        b"KoeretoejOplysningNCAPTest" => boolean(text).map(|v| details.ncap_test = Some(v)),
+        b"KoeretoejOplysningKoeretoejstand" => string(text).map(|v| details.condition = Some(v)),
+        b"KoeretoejOplysningEgnetTilTaxi" => boolean(text).map(|v| details.taxi_suitable = Some(v)),
+        b"KoeretoejOplysningTrafikskade" => boolean(text).map(|v| details.traffic_damage = Some(v)),
+        b"KoeretoejOplysningTypeAnmeldelseNummer" => {
+            string(text).map(|v| details.type_notification_number = Some(v))
+        }
+        b"KoeretoejOplysningTypeGodkendelseNummer" => {
+            string(text).map(|v| details.type_approval_number = Some(v))
+        }
+        b"KoeretoejOplysningKommentar" => string(text).map(|v| details.comment = Some(v)),
+
+        b"KoeretoejMaerkeTypeNummer" => {
+            number(text).map(|v| coded(&mut designation.make).number = Some(v))
+        }
+        b"KoeretoejMaerkeTypeNavn" => {
+            string(text).map(|v| coded(&mut designation.make).name = Some(v))
+        }
+        b"KoeretoejModelTypeNummer" => {
+            number(text).map(|v| coded(&mut designation.model).number = Some(v))
+        }
+        b"KoeretoejModelTypeNavn" => {
+            string(text).map(|v| coded(&mut designation.model).name = Some(v))
+        }
+        b"KoeretoejVariantTypeNummer" => {
+            number(text).map(|v| coded(&mut designation.variant).number = Some(v))
+        }
+        b"KoeretoejVariantTypeNavn" => {
+            string(text).map(|v| coded(&mut designation.variant).name = Some(v))
+        }
+        b"KoeretoejTypeTypeNummer" => {
+            number(text).map(|v| coded(&mut designation.vehicle_type).number = Some(v))
+        }
+        b"KoeretoejTypeTypeNavn" => {
+            string(text).map(|v| coded(&mut designation.vehicle_type).name = Some(v))
+        }
+        b"FarveTypeNummer" => number(text).map(|v| coded(&mut details.colour).number = Some(v)),
+        b"FarveTypeNavn" => string(text).map(|v| coded(&mut details.colour).name = Some(v)),
+        b"KarrosseriTypeNummer" => {
+            number(text).map(|v| coded(&mut details.body_type).number = Some(v))
+        }
+        b"KarrosseriTypeNavn" => string(text).map(|v| coded(&mut details.body_type).name = Some(v)),
+        b"NormTypeNummer" => number(text).map(|v| coded(&mut details.norm).number = Some(v)),
+        b"NormTypeNavn" => string(text).map(|v| coded(&mut details.norm).name = Some(v)),
+
+        b"KoeretoejMiljoeOplysningPartikelFilter" => {
+            boolean(text).map(|v| environment.particle_filter = Some(v))
+        }
+        b"KoeretoejMiljoeOplysningCO2Udslip" => {
+            number(text).map(|v| environment.co2_emission = Some(v))
+        }
+
+        b"KoeretoejMotorCylinderAntal" => number(text).map(|v| motor.cylinders = Some(v)),
+        b"KoeretoejMotorSlagVolumen" => number(text).map(|v| motor.displacement = Some(v)),
+        b"KoeretoejMotorSlagVolumenIkkeTilgaengelig" => {
+            boolean(text).map(|v| motor.displacement_unavailable = Some(v))
+        }
+        b"KoeretoejMotorStoersteEffekt" => number(text).map(|v| motor.max_power = Some(v)),
+        b"KoeretoejMotorStoersteEffektIkkeTilgaengelig" => {
+            boolean(text).map(|v| motor.max_power_unavailable = Some(v))
+        }
+        b"KoeretoejMotorKilometerstand" => number(text).map(|v| motor.odometer = Some(v)),
+        b"KoeretoejMotorKilometerstandDokumentation" => {
+            boolean(text).map(|v| motor.odometer_documented = Some(v))
+        }
+        b"KoeretoejMotorKilometerstandIkkeTilgaengelig" => {
+            boolean(text).map(|v| motor.odometer_unavailable = Some(v))
+        }
+        b"KoeretoejMotorInnovativTeknik" => {
+            boolean(text).map(|v| motor.innovative_technology = Some(v))
+        }
+
+        b"DrivkraftTypeNummer" => {
+            number(text).map(|v| last(&mut motor.fuels).drive_type.number = Some(v))
+        }
+        b"DrivkraftTypeNavn" => {
+            string(text).map(|v| last(&mut motor.fuels).drive_type.name = Some(v))
+        }
+        b"KoeretoejMotorKmPerLiter" => {
+            number(text).map(|v| last(&mut motor.fuels).km_per_liter = Some(v))
+        }
+        b"KoeretoejMotorElektriskForbrug" => {
+            number(text).map(|v| last(&mut motor.fuels).electric_consumption = Some(v))
+        }
+        b"KoeretoejMotorDrivmiddelPrimaer" => {
+            boolean(text).map(|v| last(&mut motor.fuels).primary = Some(v))
+        }
+
+        b"KoeretoejUdstyrAntal" => number(text).map(|v| last(equipment).count = Some(v)),
+        b"KoeretoejUdstyrTypeNummer" => {
+            number(text).map(|v| last(equipment_types).number = Some(v))
+        }
+        b"KoeretoejUdstyrTypeNavn" => string(text).map(|v| last(equipment_types).name = Some(v)),
+        b"KoeretoejUdstyrTypeVisesVedSyn" => {
+            boolean(text).map(|v| last(equipment_types).shown_at_inspection = Some(v))
+        }
+        b"KoeretoejUdstyrTypeVisesVedForespoergsel" => {
+            boolean(text).map(|v| last(equipment_types).shown_on_inquiry = Some(v))
+        }
+        b"KoeretoejUdstyrTypeVisesVedStandardOprettelse" => {
+            boolean(text).map(|v| last(equipment_types).shown_on_standard_creation = Some(v))
+        }
+
+        b"SynResultatSynsType" => string(text).map(|v| inspection(record).kind = Some(v)),
+        b"SynResultatSynsDato" => date(text).map(|v| inspection(record).date = Some(v)),
+        b"SynResultatSynsResultat" => string(text).map(|v| inspection(record).result = Some(v)),
+        b"SynResultatSynStatus" => string(text).map(|v| inspection(record).status = Some(v)),
+        b"SynResultatSynStatusDato" => date(text).map(|v| inspection(record).status_date = Some(v)),
+
+        _ => Ok(()),
+    };
+
+    result.map_err(|message| FieldError {
+        element: String::from_utf8_lossy(element).into_owned(),
+        value: text.to_string(),
+        message,
+    })
+}
+
+fn coded(slot: &mut Option<Coded>) -> &mut Coded {
+    slot.get_or_insert_with(Coded::default)
+}
+
+fn last<T: Default>(items: &mut Vec<T>) -> &mut T {
+    if items.is_empty() {
+        items.push(T::default());
+    }
+    items.last_mut().unwrap()
+}
+
+fn inspection(record: &mut VehicleRecord) -> &mut Inspection {
+    record.inspection.get_or_insert_with(Inspection::default)
+}
+
+fn string(text: &str) -> Result<String, String> {
+    Ok(text.to_string())
+}
+
+fn number<T: FromStr>(text: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    text.parse().map_err(|e: T::Err| e.to_string())
+}
+
+fn boolean(text: &str) -> Result<bool, String> {
+    match text {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+fn date(text: &str) -> Result<chrono::NaiveDate, String> {
+    parse_date(text).map_err(|e| e.to_string())
+}
+
+fn datetime(text: &str) -> Result<chrono::DateTime<chrono::FixedOffset>, String> {
+    parse_datetime(text).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dump with the given ns:Statistik bodies
+    fn document(records: &[&str]) -> String {
+        let mut xml = format!(
+            "<?xml version=\"1.0\"?>\n<ns:ESStatistikListeModtag_I xmlns:ns=\"{}\">\n<ns:StatistikSamling>\n",
+            String::from_utf8_lossy(DMR_NAMESPACE)
+        );
+        for record in records {
+            xml.push_str(&format!("<ns:Statistik>{}</ns:Statistik>\n", record));
+        }
+        xml.push_str("</ns:StatistikSamling>\n</ns:ESStatistikListeModtag_I>\n");
+        xml
+    }
+
+    fn ident(ident: u64) -> String {
+        format!("<ns:KoeretoejIdent>{}</ns:KoeretoejIdent>", ident)
+    }
+
+    fn parse(xml: &str, recovery: Recovery) -> (Vec<Result<VehicleRecord>>, ParseSummary) {
+        let mut records = XmlRecords::new(xml.as_bytes(), recovery);
+        let results = (&mut records).collect();
+        (results, records.summary())
+    }
+
+    fn idents(results: Vec<Result<VehicleRecord>>) -> Vec<u64> {
+        results.into_iter().map(|r| r.unwrap().ident).collect()
+    }
+
+    #[test]
+    fn records_in_document_order() {
+        let xml = document(&[&ident(1), &ident(2), &ident(3)]);
+        let (results, summary) = parse(&xml, Recovery::Strict);
+        assert_eq!(idents(results), [1, 2, 3]);
+        assert_eq!(
+            summary,
+            ParseSummary {
+                records: 3,
+                skipped: 0
+            }
+        );
+    }
+
+    #[test]
+    fn any_prefix() {
+        let xml = document(&[&ident(1)])
+            .replace("xmlns:ns=", "xmlns:dmr=")
+            .replace("<ns:", "<dmr:")
+            .replace("</ns:", "</dmr:");
+        let (results, _) = parse(&xml, Recovery::Strict);
+        assert_eq!(idents(results), [1]);
+    }
+
+    #[test]
+    fn resumes_after_malformed_record() {
+        let broken = format!("{}<ns:KoeretoejArtNavn>Personbil</ns:Wrong>", ident(2));
+        let xml = document(&[&ident(1), &broken, &ident(3), &ident(4)]);
+
+        let (results, summary) = parse(&xml, Recovery::Skip);
+        assert_eq!(idents(results), [1, 3, 4]);
+        assert_eq!(
+            summary,
+            ParseSummary {
+                records: 3,
+                skipped: 1
+            }
+        );
+
+        let (results, _) = parse(&xml, Recovery::Strict);
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[1], Err(Error::Xml { .. })));
+    }
+
+    #[test]
+    fn resumes_after_several_malformed_records() {
+        let broken = |n| format!("{}<ns:Bad attr=>", ident(n));
+        let xml = document(&[&broken(1), &ident(2), &broken(3), &broken(4), &ident(5)]);
+        let (results, summary) = parse(&xml, Recovery::Skip);
+        assert_eq!(idents(results), [2, 5]);
+        assert_eq!(summary.skipped, 3);
+    }
+
+    #[test]
+    fn malformed_last_record() {
+        let xml = document(&[&ident(1), "<ns:KoeretoejIdent>2</ns:KoeretoejIdent"]);
+        let (results, summary) = parse(&xml, Recovery::Skip);
+        assert_eq!(idents(results), [1]);
+        assert_eq!(summary.skipped, 1);
+    }
+
+    #[test]
+    fn record_without_ident() {
+        let xml = document(&[&ident(1), "<ns:KoeretoejArtNavn>Bus</ns:KoeretoejArtNavn>"]);
+        let (results, summary) = parse(&xml, Recovery::Skip);
+        assert_eq!(idents(results), [1]);
+        assert_eq!(summary.skipped, 1);
+
+        let (results, _) = parse(&xml, Recovery::Strict);
+        assert!(matches!(results[1], Err(Error::Schema { .. })));
+    }
+
+    #[test]
+    fn invalid_field_value() {
+        let body = format!(
+            "{}<ns:KoeretoejOplysningGrundStruktur><ns:KoeretoejOplysningAkselAntal>two</ns:KoeretoejOplysningAkselAntal></ns:KoeretoejOplysningGrundStruktur>",
+            ident(1)
+        );
+        let xml = document(&[&body]);
+
+        // Kept with the field left empty
+        let (results, summary) = parse(&xml, Recovery::Skip);
+        assert_eq!(summary.skipped, 0);
+        assert_eq!(results[0].as_ref().unwrap().details.axles, None);
+
+        let (results, _) = parse(&xml, Recovery::Strict);
+        match &results[0] {
+            Err(Error::Validation { ident, source, .. }) => {
+                assert_eq!(*ident, 1);
+                assert_eq!(source.element, "KoeretoejOplysningAkselAntal");
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vin_is_normalised() {
+        let body = format!(
+            "{}<ns:KoeretoejOplysningGrundStruktur><ns:KoeretoejOplysningStelNummer> wauzzz4f38n069602 </ns:KoeretoejOplysningStelNummer></ns:KoeretoejOplysningGrundStruktur>",
+            ident(1)
+        );
+        let (results, _) = parse(&document(&[&body]), Recovery::Strict);
+        let record = results.into_iter().next().unwrap().unwrap();
+        assert_eq!(record.details.vin.as_deref(), Some("WAUZZZ4F38N069602"));
+    }
+
+    #[test]
+    fn equipment_types_go_to_the_catalogue() {
+        let equipment = |count: u32, number: u32, name: &str| {
+            format!(
+                "<ns:KoeretoejUdstyrStruktur><ns:KoeretoejUdstyrAntal>{}</ns:KoeretoejUdstyrAntal>\
+                 <ns:KoeretoejUdstyrTypeStruktur><ns:KoeretoejUdstyrTypeNummer>{}</ns:KoeretoejUdstyrTypeNummer>\
+                 <ns:KoeretoejUdstyrTypeNavn>{}</ns:KoeretoejUdstyrTypeNavn>\
+                 <ns:KoeretoejUdstyrTypeVisesVedSyn>true</ns:KoeretoejUdstyrTypeVisesVedSyn>\
+                 </ns:KoeretoejUdstyrTypeStruktur></ns:KoeretoejUdstyrStruktur>",
+                count, number, name
+            )
+        };
+        let grund = |equipment: &[String]| {
+            format!(
+                "<ns:KoeretoejOplysningGrundStruktur><ns:KoeretoejUdstyrSamlingStruktur>\
+                 <ns:KoeretoejUdstyrSamling>{}</ns:KoeretoejUdstyrSamling>\
+                 </ns:KoeretoejUdstyrSamlingStruktur></ns:KoeretoejOplysningGrundStruktur>",
+                equipment.concat()
+            )
+        };
+        let first = format!(
+            "{}{}",
+            ident(1),
+            grund(&[
+                equipment(2, 9901, "Airbags"),
+                equipment(0, 9902, "Anhængertræk")
+            ])
+        );
+        // A later description of a known type is ignored
+        let second = format!("{}{}", ident(2), grund(&[equipment(1, 9901, "Other name")]));
+        let xml = document(&[&first, &second]);
+
+        let mut records = XmlRecords::new(xml.as_bytes(), Recovery::Strict);
+        let record = records.next().unwrap().unwrap();
+        let equipment: Vec<_> = record
+            .details
+            .equipment
+            .iter()
+            .map(|e| (e.count, e.type_number))
+            .collect();
+        assert_eq!(equipment, [(Some(2), Some(9901)), (Some(0), Some(9902))]);
+        assert_eq!(records.catalogue().len(), 2);
+
+        assert!(records.next().unwrap().is_ok());
+        let airbags = records.catalogue().get(9901).unwrap();
+        assert_eq!(airbags.name.as_deref(), Some("Airbags"));
+        assert_eq!(airbags.shown_at_inspection, Some(true));
+    }
+}
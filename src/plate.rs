@@ -0,0 +1,171 @@
+//! Danish registration plate (RegistreringNummerNummer) formats.
+
+use std::fmt;
+
+/// Longest text that fits on a Danish plate
+const MAX_LEN: usize = 7;
+
+/// Kind of plate, told apart by the layout of the registration number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlateClass {
+    /// Two letters and five digits, e.g. AB12345
+    Standard,
+    /// Free text chosen by the owner ("ønskeplade")
+    Personalised,
+    /// CD (corps diplomatique) or CC (consular) and five digits
+    Diplomatic,
+    /// Prøveskilt issued to a dealer or workshop, digits only
+    Trade,
+    /// One letter and up to five digits, the layout used before 1966
+    Historic,
+}
+
+impl PlateClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PlateClass::Standard => "standard",
+            PlateClass::Personalised => "personalised",
+            PlateClass::Diplomatic => "diplomatic",
+            PlateClass::Trade => "trade",
+            PlateClass::Historic => "historic",
+        }
+    }
+}
+
+impl fmt::Display for PlateClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a registration number cannot be a Danish plate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlateError {
+    Empty,
+    TooLong(usize),
+    InvalidCharacter(char),
+    NoLetters,
+}
+
+impl fmt::Display for PlateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlateError::Empty => f.write_str("empty plate"),
+            PlateError::TooLong(len) => {
+                write!(f, "{} characters (at most {} fit on a plate)", len, MAX_LEN)
+            }
+            PlateError::InvalidCharacter(c) => write!(f, "invalid character {:?}", c),
+            PlateError::NoLetters => f.write_str("digits only, but not a trade plate number"),
+        }
+    }
+}
+
+impl std::error::Error for PlateError {}
+
+/// Plates are stored without spaces and in upper case
+pub fn normalize(plate: &str) -> String {
+    plate
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Normalise a registration number and work out which kind of plate it is
+pub fn parse(raw: &str) -> Result<(String, PlateClass), PlateError> {
+    let plate = normalize(raw);
+    let class = classify(&plate)?;
+    Ok((plate, class))
+}
+
+/// Classify an already normalised registration number
+pub fn classify(plate: &str) -> Result<PlateClass, PlateError> {
+    let len = plate.chars().count();
+    if len == 0 {
+        return Err(PlateError::Empty);
+    }
+    if len > MAX_LEN {
+        return Err(PlateError::TooLong(len));
+    }
+    if let Some(c) = plate.chars().find(|c| !is_plate_char(*c)) {
+        return Err(PlateError::InvalidCharacter(c));
+    }
+
+    let prefix: String = plate.chars().take_while(|c| c.is_alphabetic()).collect();
+    let rest = &plate[prefix.len()..];
+    let digits_only = !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit());
+    let prefix = prefix.as_str();
+
+    let class = match (prefix.chars().count(), digits_only) {
+        (2, true) if matches!(prefix, "CD" | "CC") && rest.len() == 5 => PlateClass::Diplomatic,
+        (2, true) if is_serial(prefix) && rest.len() == 5 && !rest.starts_with('0') => {
+            PlateClass::Standard
+        }
+        (1, true) if is_serial(prefix) && rest.len() <= 5 => PlateClass::Historic,
+        (0, true) if (3..=5).contains(&len) => PlateClass::Trade,
+        _ if plate.chars().any(char::is_alphabetic) => PlateClass::Personalised,
+        _ => return Err(PlateError::NoLetters),
+    };
+    Ok(class)
+}
+
+/// Letters, digits and the Danish Æ, Ø and Å allowed on personalised plates
+fn is_plate_char(c: char) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || matches!(c, 'Æ' | 'Ø' | 'Å')
+}
+
+/// Serial letters are plain A-Z; Æ, Ø and Å only appear on personalised plates
+fn is_serial(letters: &str) -> bool {
+    letters.bytes().all(|b| b.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalises_before_classifying() {
+        assert_eq!(
+            parse(" ab 12 345 "),
+            Ok(("AB12345".to_string(), PlateClass::Standard))
+        );
+        assert_eq!(normalize("cd 12345"), "CD12345");
+    }
+
+    #[test]
+    fn classes() {
+        let cases = [
+            ("HW35733", PlateClass::Standard),
+            ("CD12345", PlateClass::Diplomatic),
+            ("CC12345", PlateClass::Diplomatic),
+            ("A1234", PlateClass::Historic),
+            ("K1", PlateClass::Historic),
+            ("1234", PlateClass::Trade),
+            ("12345", PlateClass::Trade),
+            ("MORMOR", PlateClass::Personalised),
+            ("ÆBLE1", PlateClass::Personalised),
+            // A standard serial never starts with 0
+            ("AB01234", PlateClass::Personalised),
+            // Serials are two letters and five digits exactly
+            ("AB1234", PlateClass::Personalised),
+            ("ABC1234", PlateClass::Personalised),
+            ("ÆØ12345", PlateClass::Personalised),
+            ("1A", PlateClass::Personalised),
+        ];
+        for (plate, class) in cases {
+            assert_eq!(classify(plate), Ok(class), "{}", plate);
+        }
+    }
+
+    #[test]
+    fn invalid_plates() {
+        assert_eq!(classify(""), Err(PlateError::Empty));
+        assert_eq!(classify("ABCD12345"), Err(PlateError::TooLong(9)));
+        assert_eq!(classify("ÆØÅÆØÅÆ"), Ok(PlateClass::Personalised));
+        assert_eq!(classify("ÆØÅÆØÅÆØ"), Err(PlateError::TooLong(8)));
+        assert_eq!(classify("AB-1234"), Err(PlateError::InvalidCharacter('-')));
+        assert_eq!(classify("ab12345"), Err(PlateError::InvalidCharacter('a')));
+        assert_eq!(classify("12"), Err(PlateError::NoLetters));
+        assert_eq!(classify("123456"), Err(PlateError::NoLetters));
+    }
+}
@@ -0,0 +1,168 @@
+//! Data-quality findings raised while importing a dump.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Datelike;
+
+use crate::model::VehicleRecord;
+use crate::vin;
+
+/// Name of the findings report written next to the store
+pub const FINDINGS_FILE: &str = "findings.tsv";
+
+/// A field of a record that was accepted but looks wrong
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub ident: u64,
+    pub element: &'static str,
+    pub value: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "KoeretoejIdent {}: {} {:?} {}",
+            self.ident, self.element, self.value, self.message
+        )
+    }
+}
+
+/// Run every data-quality check on a record
+pub fn check_record(record: &VehicleRecord) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let details = &record.details;
+
+    if let Some(stel_nummer) = &details.vin {
+        // Chassis numbers from before ISO 3779 (1981) follow no common format
+        let pre_standard = details
+            .first_registration
+            .is_some_and(|date| date.year() < 1981);
+        if !(pre_standard && stel_nummer.len() != 17) {
+            let make = details
+                .designation
+                .make
+                .as_ref()
+                .and_then(|make| make.name.as_deref());
+            for problem in vin::validate(stel_nummer, make) {
+                findings.push(Finding {
+                    ident: record.ident,
+                    element: "KoeretoejOplysningStelNummer",
+                    value: stel_nummer.clone(),
+                    message: problem.to_string(),
+                });
+            }
+        }
+    }
+
+    findings
+}
+
+/// Findings kept in memory for a summary, besides the full report
+const SAMPLE_SIZE: usize = 5;
+
+/// Writes findings as tab separated values while a dump is imported, so
+/// they are not held in memory. The report goes to a temporary file and
+/// replaces any earlier one at [`FindingsWriter::finish`].
+pub struct FindingsWriter {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    out: BufWriter<File>,
+    count: usize,
+    sample: Vec<Finding>,
+}
+
+impl FindingsWriter {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<FindingsWriter> {
+        let path = path.as_ref().to_path_buf();
+        let tmp_path = path.with_extension("tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        writeln!(out, "ident\telement\tvalue\tmessage")?;
+        Ok(FindingsWriter {
+            path,
+            tmp_path,
+            out,
+            count: 0,
+            sample: Vec::new(),
+        })
+    }
+
+    pub fn write(&mut self, findings: Vec<Finding>) -> io::Result<()> {
+        for finding in findings {
+            writeln!(
+                self.out,
+                "{}\t{}\t{}\t{}",
+                finding.ident, finding.element, finding.value, finding.message
+            )?;
+            self.count += 1;
+            if self.sample.len() < SAMPLE_SIZE {
+                self.sample.push(finding);
+            }
+        }
+        Ok(())
+    }
+
+    /// Findings written so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The first few findings written
+    pub fn sample(&self) -> &[Finding] {
+        &self.sample
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the report into place
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        fs::rename(&self.tmp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(ident: u64) -> Finding {
+        Finding {
+            ident,
+            element: "KoeretoejOplysningStelNummer",
+            value: "ABC".to_string(),
+            message: "length is 3 (expected 17)".to_string(),
+        }
+    }
+
+    #[test]
+    fn findings_are_streamed_to_the_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FINDINGS_FILE);
+        fs::write(&path, "old report").unwrap();
+
+        let mut writer = FindingsWriter::create(&path).unwrap();
+        for ident in 0..8 {
+            writer.write(vec![finding(ident)]).unwrap();
+        }
+        writer.write(Vec::new()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old report");
+        writer.finish().unwrap();
+
+        assert_eq!(writer.count(), 8);
+        assert_eq!(writer.sample().len(), SAMPLE_SIZE);
+        let report = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "ident\telement\tvalue\tmessage");
+        assert_eq!(
+            lines[8],
+            "7\tKoeretoejOplysningStelNummer\tABC\tlength is 3 (expected 17)"
+        );
+    }
+}
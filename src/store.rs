@@ -0,0 +1,885 @@
+//! Persistent on-disk vehicle database.
+//!
+//! A store is a directory holding three files: `vehicles.dat`, an
+//! append-only log of encoded records, `index.dat`, which maps every
+//! KoeretoejIdent to the offset of its latest record along with its plate
+//! and VIN, and `catalogue.dat`, the equipment types records refer to by
+//! number. The plate and VIN indexes are rebuilt from the ident table on
+//! open.
+//!
+//! Incremental imports append changed records to the data file and note
+//! every added, changed and removed vehicle in `changes.log`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod codec;
+
+use codec::{Decode, Encode};
+
+use chrono::Utc;
+
+use crate::equipment::Catalogue;
+use crate::model::VehicleRecord;
+use crate::parser::ParseSummary;
+
+/// Store directory used when `AUTOPLATE_DB` is not set
+pub const DEFAULT_STORE_DIR: &str = "autoplate-db";
+
+const DATA_FILE: &str = "vehicles.dat";
+const INDEX_FILE: &str = "index.dat";
+const CATALOGUE_FILE: &str = "catalogue.dat";
+const CHANGE_LOG_FILE: &str = "changes.log";
+const DATA_MAGIC: &[u8; 8] = b"APVDATA\0";
+const INDEX_MAGIC: &[u8; 8] = b"APVINDX\0";
+const CATALOGUE_MAGIC: &[u8; 8] = b"APVCTLG\0";
+const FORMAT_VERSION: u32 = 6;
+
+/// magic + version + generation
+const HEADER_LEN: u64 = 8 + 4 + 8;
+
+/// Where the latest version of a vehicle lives
+#[derive(Debug, Clone)]
+struct Entry {
+    offset: u64,
+    /// Hash of the encoded record, used to skip unchanged vehicles
+    hash: u64,
+    plate: Option<String>,
+    vin: Option<String>,
+}
+
+impl Entry {
+    fn new(offset: u64, hash: u64, record: &VehicleRecord) -> Self {
+        Entry {
+            offset,
+            hash,
+            plate: record.plate.clone(),
+            vin: record.details.vin.clone(),
+        }
+    }
+}
+
+impl Encode for Entry {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.offset.encode(out);
+        self.hash.encode(out);
+        self.plate.encode(out);
+        self.vin.encode(out);
+    }
+}
+
+impl Decode for Entry {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(Entry {
+            offset: Decode::decode(input)?,
+            hash: Decode::decode(input)?,
+            plate: Decode::decode(input)?,
+            vin: Decode::decode(input)?,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    /// Ties the index to the data file it was written for
+    generation: u64,
+    /// Committed length of the data file; anything after it is discarded
+    data_len: u64,
+    by_ident: BTreeMap<u64, Entry>,
+    by_plate: HashMap<String, Vec<u64>>,
+    by_vin: HashMap<String, Vec<u64>>,
+}
+
+impl Index {
+    fn new(generation: u64) -> Self {
+        Index {
+            generation,
+            data_len: HEADER_LEN,
+            ..Index::default()
+        }
+    }
+
+    fn insert(&mut self, ident: u64, entry: Entry) {
+        self.remove(ident);
+        if let Some(plate) = &entry.plate {
+            self.by_plate.entry(plate.clone()).or_default().push(ident);
+        }
+        if let Some(vin) = &entry.vin {
+            self.by_vin.entry(vin.clone()).or_default().push(ident);
+        }
+        self.by_ident.insert(ident, entry);
+    }
+
+    fn remove(&mut self, ident: u64) {
+        if let Some(old) = self.by_ident.remove(&ident) {
+            unlink(&mut self.by_plate, old.plate.as_deref(), ident);
+            unlink(&mut self.by_vin, old.vin.as_deref(), ident);
+        }
+    }
+
+    fn read(path: &Path) -> io::Result<Index> {
+        let bytes = fs::read(path)?;
+        let mut input = bytes.as_slice();
+        read_header(&mut input, INDEX_MAGIC)?;
+
+        let mut index = Index::new(u64::decode(&mut input)?);
+        index.data_len = u64::decode(&mut input)?;
+        let entries: Vec<(u64, Entry)> = Decode::decode(&mut input)?;
+        for (ident, entry) in entries {
+            index.insert(ident, entry);
+        }
+        Ok(index)
+    }
+
+    /// Write the index next to `path` and move it into place
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let mut out = Vec::new();
+        write_header(&mut out, INDEX_MAGIC);
+        self.generation.encode(&mut out);
+        self.data_len.encode(&mut out);
+        (self.by_ident.len() as u32).encode(&mut out);
+        for (ident, entry) in &self.by_ident {
+            ident.encode(&mut out);
+            entry.encode(&mut out);
+        }
+        write_file(path, &out)
+    }
+}
+
+fn read_catalogue(path: &Path, generation: u64) -> io::Result<Catalogue> {
+    let bytes = fs::read(path)?;
+    let mut input = bytes.as_slice();
+    read_header(&mut input, CATALOGUE_MAGIC)?;
+    if u64::decode(&mut input)? != generation {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "store catalogue does not match its data file, re-import to rebuild it",
+        ));
+    }
+    Catalogue::decode(&mut input)
+}
+
+/// Write the catalogue next to `path` and move it into place
+fn write_catalogue(path: &Path, generation: u64, catalogue: &Catalogue) -> io::Result<()> {
+    let mut out = Vec::new();
+    write_header(&mut out, CATALOGUE_MAGIC);
+    generation.encode(&mut out);
+    catalogue.encode(&mut out);
+    write_file(path, &out)
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)
+}
+
+fn unlink(map: &mut HashMap<String, Vec<u64>>, key: Option<&str>, ident: u64) {
+    if let Some(key) = key {
+        if let Some(idents) = map.get_mut(key) {
+            idents.retain(|i| *i != ident);
+            if idents.is_empty() {
+                map.remove(key);
+            }
+        }
+    }
+}
+
+fn write_header(out: &mut Vec<u8>, magic: &[u8; 8]) {
+    out.extend_from_slice(magic);
+    FORMAT_VERSION.encode(out);
+}
+
+fn read_header(input: &mut &[u8], magic: &[u8; 8]) -> io::Result<()> {
+    if input.len() < magic.len() || &input[..magic.len()] != magic {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an autoplate store file",
+        ));
+    }
+    *input = &input[magic.len()..];
+    let version = u32::decode(input)?;
+    if version != FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "store format version {} is not supported (expected {}), re-import to rebuild it",
+                version, FORMAT_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// An open vehicle database
+pub struct Store {
+    dir: PathBuf,
+    data: File,
+    index: Index,
+    catalogue: Catalogue,
+    /// Opened with [`Store::open_for_update`]
+    writable: bool,
+}
+
+impl Store {
+    /// Open the store in `dir` for reading. Nothing on disk is changed, and
+    /// a missing store is an [`io::ErrorKind::NotFound`] error.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Store> {
+        let dir = dir.as_ref();
+        if !dir.join(INDEX_FILE).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No store in {}, import an archive first", dir.display()),
+            ));
+        }
+        Store::open_with(dir, false)
+    }
+
+    /// Open the store in `dir` for [`Store::update`], creating an empty one
+    /// if there is none. Data left after the last commit is cut off.
+    pub fn open_for_update(dir: impl AsRef<Path>) -> io::Result<Store> {
+        let dir = dir.as_ref();
+        if !dir.join(INDEX_FILE).exists() {
+            Store::rebuild(dir)?.finish()?;
+        }
+        let store = Store::open_with(dir, true)?;
+        // Drop anything written after the last commit
+        store.data.set_len(store.index.data_len)?;
+        Ok(store)
+    }
+
+    fn open_with(dir: &Path, writable: bool) -> io::Result<Store> {
+        let index = Index::read(&dir.join(INDEX_FILE))?;
+        let mut data = OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .open(dir.join(DATA_FILE))?;
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        data.read_exact(&mut header)?;
+        let mut input = &header[..];
+        read_header(&mut input, DATA_MAGIC)?;
+        if u64::decode(&mut input)? != index.generation {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "store index does not match its data file, re-import to rebuild it",
+            ));
+        }
+
+        let catalogue = read_catalogue(&dir.join(CATALOGUE_FILE), index.generation)?;
+        Ok(Store {
+            dir: dir.to_path_buf(),
+            data,
+            index,
+            catalogue,
+            writable,
+        })
+    }
+
+    /// Start replacing the whole contents of the store in `dir`. The
+    /// existing data stays readable until [`StoreWriter::finish`].
+    pub fn rebuild(dir: impl AsRef<Path>) -> io::Result<StoreWriter> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let generation = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+
+        let tmp_path = dir.join(DATA_FILE).with_extension("tmp");
+        let mut data = BufWriter::new(File::create(&tmp_path)?);
+        let mut header = Vec::new();
+        write_header(&mut header, DATA_MAGIC);
+        generation.encode(&mut header);
+        data.write_all(&header)?;
+
+        Ok(StoreWriter {
+            dir: dir.to_path_buf(),
+            tmp_path,
+            data,
+            index: Index::new(generation),
+            catalogue: Catalogue::new(),
+        })
+    }
+
+    /// Start an incremental import of the dump named `source`. Only
+    /// vehicles that differ from the stored version are written. The store
+    /// must have been opened with [`Store::open_for_update`].
+    pub fn update(&mut self, source: &str) -> io::Result<StoreUpdate<'_>> {
+        if !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "store was opened read-only",
+            ));
+        }
+        let mut data = self.data.try_clone()?;
+        data.seek(SeekFrom::Start(self.index.data_len))?;
+
+        Ok(StoreUpdate {
+            data: BufWriter::new(data),
+            source: source.to_string(),
+            seen: HashSet::new(),
+            catalogue: Catalogue::new(),
+            changes: Vec::new(),
+            unchanged: 0,
+            store: self,
+        })
+    }
+
+    /// Number of vehicles in the store
+    pub fn len(&self) -> usize {
+        self.index.by_ident.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.by_ident.is_empty()
+    }
+
+    /// Number of distinct VINs in the store
+    pub fn vin_count(&self) -> usize {
+        self.index.by_vin.len()
+    }
+
+    /// The equipment types the vehicles in the store refer to
+    pub fn catalogue(&self) -> &Catalogue {
+        &self.catalogue
+    }
+
+    /// All plates in the store, unordered
+    pub fn plates(&self) -> impl Iterator<Item = &str> {
+        self.index.by_plate.keys().map(String::as_str)
+    }
+
+    /// Every vehicle in the store, in KoeretoejIdent order
+    pub fn records(&self) -> impl Iterator<Item = io::Result<VehicleRecord>> + '_ {
+        self.index
+            .by_ident
+            .values()
+            .map(|entry| self.read_at(entry.offset))
+    }
+
+    pub fn get(&self, ident: u64) -> io::Result<Option<VehicleRecord>> {
+        match self.index.by_ident.get(&ident) {
+            Some(entry) => self.read_at(entry.offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Every vehicle that carries `plate`. A plate can be reissued, so this
+    /// may return more than one record.
+    pub fn find_by_plate(&self, plate: &str) -> io::Result<Vec<VehicleRecord>> {
+        self.read_all(self.index.by_plate.get(plate))
+    }
+
+    /// Every vehicle registered with stelnummer `vin`
+    pub fn find_by_vin(&self, vin: &str) -> io::Result<Vec<VehicleRecord>> {
+        self.read_all(self.index.by_vin.get(vin))
+    }
+
+    fn read_all(&self, idents: Option<&Vec<u64>>) -> io::Result<Vec<VehicleRecord>> {
+        idents
+            .into_iter()
+            .flatten()
+            .filter_map(|ident| self.get(*ident).transpose())
+            .collect()
+    }
+
+    /// Read the record at `offset` without moving the file cursor, so
+    /// that threads sharing the store do not disturb each other's reads
+    fn read_at(&self, offset: u64) -> io::Result<VehicleRecord> {
+        let mut len = [0u8; 4];
+        read_exact_at(&self.data, &mut len, offset)?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        read_exact_at(&self.data, &mut bytes, offset + 4)?;
+        VehicleRecord::decode(&mut bytes.as_slice())
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a complete new store, see [`Store::rebuild`]
+pub struct StoreWriter {
+    dir: PathBuf,
+    tmp_path: PathBuf,
+    data: BufWriter<File>,
+    index: Index,
+    catalogue: Catalogue,
+}
+
+impl StoreWriter {
+    /// Add a record, and the equipment types it refers to from
+    /// `catalogue`. A later record with the same KoeretoejIdent replaces
+    /// an earlier one.
+    pub fn insert(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()> {
+        self.catalogue.adopt(record, catalogue);
+        let (bytes, hash) = encode_record(record);
+        let offset = self.index.data_len;
+        self.index.data_len += write_record(&mut self.data, &bytes)?;
+        self.index
+            .insert(record.ident, Entry::new(offset, hash, record));
+        Ok(())
+    }
+
+    /// Move the new data and index into place and open the result
+    pub fn finish(self) -> io::Result<Store> {
+        let data = self.data.into_inner().map_err(|e| e.into_error())?;
+        data.sync_all()?;
+        drop(data);
+
+        fs::rename(&self.tmp_path, self.dir.join(DATA_FILE))?;
+        write_catalogue(
+            &self.dir.join(CATALOGUE_FILE),
+            self.index.generation,
+            &self.catalogue,
+        )?;
+        self.index.write(&self.dir.join(INDEX_FILE))?;
+        Store::open(&self.dir)
+    }
+}
+
+/// How a vehicle differs between two successive dumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Changed => "changed",
+            ChangeKind::Removed => "removed",
+        })
+    }
+}
+
+/// Counts from a finished incremental import
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateSummary {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    /// Vehicles missing from the dump were left in place because damaged
+    /// records were skipped, and may have been among them
+    pub removal_skipped: bool,
+}
+
+/// An incremental import in progress, see [`Store::update`]
+pub struct StoreUpdate<'a> {
+    store: &'a mut Store,
+    data: BufWriter<File>,
+    source: String,
+    seen: HashSet<u64>,
+    /// Equipment types the new dump describes
+    catalogue: Catalogue,
+    changes: Vec<(u64, ChangeKind)>,
+    unchanged: usize,
+}
+
+impl StoreUpdate<'_> {
+    /// Compare a record from the new dump with the stored version and
+    /// write it if it is new or different. The descriptions in `catalogue`
+    /// of the equipment types it refers to replace the stored ones.
+    pub fn apply(&mut self, record: &VehicleRecord, catalogue: &Catalogue) -> io::Result<()> {
+        self.seen.insert(record.ident);
+        self.catalogue.adopt(record, catalogue);
+
+        let (bytes, hash) = encode_record(record);
+        let kind = match self.store.index.by_ident.get(&record.ident) {
+            Some(entry) if entry.hash == hash => {
+                self.unchanged += 1;
+                return Ok(());
+            }
+            Some(_) => ChangeKind::Changed,
+            None => ChangeKind::Added,
+        };
+
+        let index = &mut self.store.index;
+        let offset = index.data_len;
+        index.data_len += write_record(&mut self.data, &bytes)?;
+        index.insert(record.ident, Entry::new(offset, hash, record));
+        self.changes.push((record.ident, kind));
+        Ok(())
+    }
+
+    /// Remove every vehicle missing from the new dump, commit the index
+    /// and append this import to the change log. Every record of the dump
+    /// must have been applied, or the ones left out count as removed.
+    ///
+    /// `parsed` is the outcome of reading the dump. When damaged records
+    /// were skipped, nothing is removed: a skipped record may be a vehicle
+    /// that is still registered.
+    pub fn finish(mut self, parsed: ParseSummary) -> io::Result<UpdateSummary> {
+        let index = &mut self.store.index;
+        let removal_skipped = parsed.skipped > 0;
+        if !removal_skipped {
+            let removed: Vec<u64> = index
+                .by_ident
+                .keys()
+                .filter(|ident| !self.seen.contains(ident))
+                .copied()
+                .collect();
+            for ident in removed {
+                index.remove(ident);
+                self.changes.push((ident, ChangeKind::Removed));
+            }
+        }
+
+        let data = self.data.into_inner().map_err(|e| e.into_error())?;
+        data.sync_all()?;
+        // Types only removed vehicles referred to are kept
+        self.store.catalogue.update(&self.catalogue);
+        write_catalogue(
+            &self.store.dir.join(CATALOGUE_FILE),
+            index.generation,
+            &self.store.catalogue,
+        )?;
+        index.write(&self.store.dir.join(INDEX_FILE))?;
+
+        let mut summary = UpdateSummary {
+            unchanged: self.unchanged,
+            removal_skipped,
+            ..UpdateSummary::default()
+        };
+        let timestamp = Utc::now().to_rfc3339();
+        let mut log = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.store.dir.join(CHANGE_LOG_FILE))?,
+        );
+        for (ident, kind) in &self.changes {
+            match kind {
+                ChangeKind::Added => summary.added += 1,
+                ChangeKind::Changed => summary.changed += 1,
+                ChangeKind::Removed => summary.removed += 1,
+            }
+            writeln!(log, "{}\t{}\t{}\t{}", timestamp, self.source, kind, ident)?;
+        }
+        log.flush()?;
+
+        Ok(summary)
+    }
+}
+
+/// Encode a record and hash the result
+fn encode_record(record: &VehicleRecord) -> (Vec<u8>, u64) {
+    let mut bytes = Vec::with_capacity(1024);
+    record.encode(&mut bytes);
+    let hash = fnv1a(&bytes);
+    (bytes, hash)
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Append one length-prefixed record, returning the number of bytes written
+fn write_record(out: &mut impl Write, bytes: &[u8]) -> io::Result<u64> {
+    out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    out.write_all(bytes)?;
+    Ok(4 + bytes.len() as u64)
+}
+
+/// Append a record in the data file's encoding, for temporary files that
+/// are read back with [`read_record`]
+pub(crate) fn append_record(out: &mut impl Write, record: &VehicleRecord) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(1024);
+    record.encode(&mut bytes);
+    write_record(out, &bytes).map(|_| ())
+}
+
+/// Read one length-prefixed record
+pub(crate) fn read_record(input: &mut impl Read) -> io::Result<VehicleRecord> {
+    let mut len = [0u8; 4];
+    input.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+    input.read_exact(&mut bytes)?;
+    VehicleRecord::decode(&mut bytes.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Equipment, EquipmentType};
+
+    fn vehicle(ident: u64, plate: &str) -> VehicleRecord {
+        VehicleRecord {
+            ident,
+            plate: Some(plate.to_string()),
+            ..VehicleRecord::default()
+        }
+    }
+
+    fn build(dir: &Path, records: &[VehicleRecord]) {
+        let mut writer = Store::rebuild(dir).unwrap();
+        for record in records {
+            writer.insert(record, &Catalogue::new()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn open_missing_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let error = Store::open(&missing).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn open_leaves_data_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        build(dir.path(), &[vehicle(1, "AB12345")]);
+        let data = dir.path().join(DATA_FILE);
+        OpenOptions::new()
+            .append(true)
+            .open(&data)
+            .unwrap()
+            .write_all(b"uncommitted")
+            .unwrap();
+        let len = fs::metadata(&data).unwrap().len();
+
+        let mut store = Store::open(dir.path()).unwrap();
+        assert_eq!(store.find_by_plate("AB12345").unwrap().len(), 1);
+        assert!(store.update("dump").is_err());
+        drop(store);
+        assert_eq!(fs::metadata(&data).unwrap().len(), len);
+
+        Store::open_for_update(dir.path()).unwrap();
+        assert!(fs::metadata(&data).unwrap().len() < len);
+    }
+
+    #[test]
+    fn threads_share_a_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let records: Vec<_> = (1..=200)
+            .map(|ident| {
+                let mut record = vehicle(ident, &format!("AB{:05}", ident));
+                // Records of different lengths
+                record.details.comment = Some("x".repeat(ident as usize));
+                record
+            })
+            .collect();
+        build(dir.path(), &records);
+
+        let store = Store::open(dir.path()).unwrap();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let (store, records) = (&store, &records);
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        for record in records.iter().skip(thread).step_by(3) {
+                            assert_eq!(store.get(record.ident).unwrap().as_ref(), Some(record));
+                            let plate = record.plate.as_deref().unwrap();
+                            assert_eq!(
+                                store.find_by_plate(plate).unwrap(),
+                                std::slice::from_ref(record)
+                            );
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    fn update(dir: &Path, records: &[VehicleRecord]) -> UpdateSummary {
+        let mut store = Store::open_for_update(dir).unwrap();
+        let mut update = store.update("dump-2").unwrap();
+        for record in records {
+            update.apply(record, &Catalogue::new()).unwrap();
+        }
+        update
+            .finish(ParseSummary {
+                records: records.len(),
+                skipped: 0,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn update_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        build(
+            dir.path(),
+            &[
+                vehicle(1, "AB12345"),
+                vehicle(2, "CD12345"),
+                vehicle(3, "EF12345"),
+            ],
+        );
+
+        let summary = update(
+            dir.path(),
+            &[
+                vehicle(1, "AB12345"),
+                vehicle(2, "XY98765"),
+                vehicle(4, "GH12345"),
+            ],
+        );
+        assert_eq!(
+            (
+                summary.added,
+                summary.changed,
+                summary.removed,
+                summary.unchanged
+            ),
+            (1, 1, 1, 1)
+        );
+
+        let store = Store::open(dir.path()).unwrap();
+        assert_eq!(store.len(), 3);
+        assert!(store.get(3).unwrap().is_none());
+        assert!(store.find_by_plate("CD12345").unwrap().is_empty());
+        assert_eq!(store.find_by_plate("XY98765").unwrap()[0].ident, 2);
+        assert_eq!(
+            store.get(4).unwrap().unwrap().plate.as_deref(),
+            Some("GH12345")
+        );
+
+        let log = fs::read_to_string(dir.path().join(CHANGE_LOG_FILE)).unwrap();
+        let changes: Vec<(&str, &str, &str)> = log
+            .lines()
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (fields[1], fields[2], fields[3])
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("dump-2", "changed", "2"),
+                ("dump-2", "added", "4"),
+                ("dump-2", "removed", "3")
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_update_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let records = [vehicle(1, "AB12345"), vehicle(2, "CD12345")];
+        build(dir.path(), &records);
+        let len = fs::metadata(dir.path().join(DATA_FILE)).unwrap().len();
+
+        let summary = update(dir.path(), &records);
+        assert_eq!(summary.unchanged, 2);
+        assert_eq!(summary.added + summary.changed + summary.removed, 0);
+        assert_eq!(fs::metadata(dir.path().join(DATA_FILE)).unwrap().len(), len);
+        let log = fs::read_to_string(dir.path().join(CHANGE_LOG_FILE)).unwrap();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn skipped_records_are_not_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        build(dir.path(), &[vehicle(1, "AB12345"), vehicle(2, "CD12345")]);
+
+        let mut store = Store::open_for_update(dir.path()).unwrap();
+        let mut update = store.update("dump-2").unwrap();
+        update
+            .apply(&vehicle(1, "AB12345"), &Catalogue::new())
+            .unwrap();
+        let summary = update
+            .finish(ParseSummary {
+                records: 1,
+                skipped: 1,
+            })
+            .unwrap();
+        assert!(summary.removal_skipped);
+        assert_eq!(summary.removed, 0);
+        drop(store);
+
+        let store = Store::open(dir.path()).unwrap();
+        assert_eq!(store.len(), 2);
+        let log = fs::read_to_string(dir.path().join(CHANGE_LOG_FILE)).unwrap();
+        assert!(!log.contains("removed"));
+    }
+
+    #[test]
+    fn open_for_update_creates_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open_for_update(dir.path().join("new")).unwrap();
+        assert!(store.is_empty());
+    }
+
+    fn equipment_types(airbag_name: &str) -> Catalogue {
+        let mut catalogue = Catalogue::new();
+        for (number, name) in [(9901, airbag_name), (9902, "Anhængertræk")] {
+            catalogue.insert(EquipmentType {
+                number: Some(number),
+                name: Some(name.to_string()),
+                ..EquipmentType::default()
+            });
+        }
+        catalogue
+    }
+
+    #[test]
+    fn equipment_types_are_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut car = vehicle(1, "AB12345");
+        car.details.equipment = vec![Equipment {
+            count: Some(2),
+            type_number: Some(9901),
+        }];
+        let mut writer = Store::rebuild(dir.path()).unwrap();
+        writer.insert(&car, &equipment_types("Airbags")).unwrap();
+        writer.finish().unwrap();
+
+        let store = Store::open(dir.path()).unwrap();
+        assert_eq!(store.get(1).unwrap().unwrap(), car);
+        // Only the types a vehicle refers to are kept
+        let names: Vec<_> = store.catalogue().types().map(|t| t.name.clone()).collect();
+        assert_eq!(names, [Some("Airbags".to_string())]);
+        drop(store);
+
+        // A new description does not change the vehicle, but replaces the
+        // stored one
+        let mut store = Store::open_for_update(dir.path()).unwrap();
+        let mut update = store.update("dump-2").unwrap();
+        update
+            .apply(&car, &equipment_types("Airbags  foran"))
+            .unwrap();
+        let summary = update.finish(ParseSummary::default()).unwrap();
+        assert_eq!(summary.unchanged, 1);
+        drop(store);
+
+        let store = Store::open(dir.path()).unwrap();
+        let airbags = store.catalogue().get(9901).unwrap();
+        assert_eq!(airbags.name.as_deref(), Some("Airbags foran"));
+    }
+}
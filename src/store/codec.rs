@@ -0,0 +1,569 @@
+//! Compact binary encoding of vehicle records for the on-disk store.
+//!
+//! Fields are written in declaration order without names, so any change to
+//! the model must bump `FORMAT_VERSION` in the store.
+
+use std::io;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone, Utc};
+
+use crate::equipment::Catalogue;
+use crate::fuel::Drivetrain;
+use crate::model::{
+    Coded, Decimal, Designation, Environment, Equipment, EquipmentType, Fuel, Inspection, Motor,
+    Status, VehicleDetails, VehicleRecord,
+};
+use crate::plate::PlateClass;
+
+pub trait Encode {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+pub trait Decode: Sized {
+    fn decode(input: &mut &[u8]) -> io::Result<Self>;
+}
+
+fn corrupt(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("corrupt store: {}", what),
+    )
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if input.len() < n {
+        return Err(corrupt("unexpected end of record"));
+    }
+    let (head, tail) = input.split_at(n);
+    *input = tail;
+    Ok(head)
+}
+
+macro_rules! int_codec {
+    ($($ty:ty),*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode(input: &mut &[u8]) -> io::Result<Self> {
+                let bytes = take(input, std::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+int_codec!(u8, u32, u64, i32, i64);
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u8).encode(out);
+    }
+}
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupt("invalid bool")),
+        }
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let len = u32::decode(input)? as usize;
+        let bytes = take(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt("invalid UTF-8"))
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => 0u8.encode(out),
+            Some(value) => {
+                1u8.encode(out);
+                value.encode(out);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match u8::decode(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            _ => Err(corrupt("invalid option tag")),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let len = u32::decode(input)? as usize;
+        // Every item takes at least one byte, so this bounds the allocation
+        let mut items = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            items.push(T::decode(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl Encode for NaiveDate {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.num_days_from_ce().encode(out);
+    }
+}
+
+impl Decode for NaiveDate {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        NaiveDate::from_num_days_from_ce_opt(i32::decode(input)?)
+            .ok_or_else(|| corrupt("invalid date"))
+    }
+}
+
+impl Encode for DateTime<FixedOffset> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.timestamp().encode(out);
+        self.timestamp_subsec_nanos().encode(out);
+        self.offset().local_minus_utc().encode(out);
+    }
+}
+
+impl Decode for DateTime<FixedOffset> {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let secs = i64::decode(input)?;
+        let nanos = u32::decode(input)?;
+        let offset =
+            FixedOffset::east_opt(i32::decode(input)?).ok_or_else(|| corrupt("invalid offset"))?;
+        let utc = Utc
+            .timestamp_opt(secs, nanos)
+            .single()
+            .ok_or_else(|| corrupt("invalid timestamp"))?;
+        Ok(utc.with_timezone(&offset))
+    }
+}
+
+impl Encode for Decimal {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.mantissa().encode(out);
+        self.scale().encode(out);
+    }
+}
+
+impl Decode for Decimal {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(Decimal::new(i64::decode(input)?, u32::decode(input)?))
+    }
+}
+
+impl Encode for Status {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_dmr().to_string().encode(out);
+    }
+}
+
+impl Decode for Status {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(Status::from(String::decode(input)?.as_str()))
+    }
+}
+
+impl Encode for PlateClass {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            PlateClass::Standard => 0,
+            PlateClass::Personalised => 1,
+            PlateClass::Diplomatic => 2,
+            PlateClass::Trade => 3,
+            PlateClass::Historic => 4,
+        };
+        tag.encode(out);
+    }
+}
+
+impl Decode for PlateClass {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match u8::decode(input)? {
+            0 => Ok(PlateClass::Standard),
+            1 => Ok(PlateClass::Personalised),
+            2 => Ok(PlateClass::Diplomatic),
+            3 => Ok(PlateClass::Trade),
+            4 => Ok(PlateClass::Historic),
+            _ => Err(corrupt("invalid plate class")),
+        }
+    }
+}
+
+impl Encode for Drivetrain {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            Drivetrain::Petrol => 0,
+            Drivetrain::Diesel => 1,
+            Drivetrain::Electric => 2,
+            Drivetrain::PluginHybrid => 3,
+            Drivetrain::Hybrid => 4,
+            Drivetrain::Gas => 5,
+            Drivetrain::Hydrogen => 6,
+            Drivetrain::Other => 7,
+        };
+        tag.encode(out);
+    }
+}
+
+impl Decode for Drivetrain {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match u8::decode(input)? {
+            0 => Ok(Drivetrain::Petrol),
+            1 => Ok(Drivetrain::Diesel),
+            2 => Ok(Drivetrain::Electric),
+            3 => Ok(Drivetrain::PluginHybrid),
+            4 => Ok(Drivetrain::Hybrid),
+            5 => Ok(Drivetrain::Gas),
+            6 => Ok(Drivetrain::Hydrogen),
+            7 => Ok(Drivetrain::Other),
+            _ => Err(corrupt("invalid drivetrain")),
+        }
+    }
+}
+
+macro_rules! struct_codec {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl Encode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                $(self.$field.encode(out);)*
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode(input: &mut &[u8]) -> io::Result<Self> {
+                Ok($ty {
+                    $($field: Decode::decode(input)?,)*
+                })
+            }
+        }
+    };
+}
+
+struct_codec!(Coded { number, name });
+
+struct_codec!(VehicleRecord {
+    ident,
+    kind,
+    usage,
+    plate,
+    plate_class,
+    plate_expiry,
+    details,
+    inspection,
+    registration_status,
+    registration_status_date,
+});
+
+struct_codec!(VehicleDetails {
+    created_from,
+    status,
+    status_date,
+    first_registration,
+    vin,
+    vin_location,
+    model_year,
+    total_weight,
+    technical_total_weight,
+    curb_weight_min,
+    curb_weight_max,
+    train_weight,
+    axles,
+    driving_axles,
+    seats_min,
+    seats_max,
+    doors,
+    coupling_possible,
+    coupling_weight_unbraked,
+    coupling_weight_braked,
+    ncap_test,
+    condition,
+    taxi_suitable,
+    traffic_damage,
+    type_notification_number,
+    type_approval_number,
+    comment,
+    designation,
+    colour,
+    body_type,
+    norm,
+    environment,
+    motor,
+    equipment,
+});
+
+struct_codec!(Designation {
+    make,
+    model,
+    variant,
+    vehicle_type,
+});
+
+struct_codec!(Environment {
+    particle_filter,
+    co2_emission,
+});
+
+struct_codec!(Motor {
+    cylinders,
+    displacement,
+    displacement_unavailable,
+    max_power,
+    max_power_unavailable,
+    odometer,
+    odometer_documented,
+    odometer_unavailable,
+    innovative_technology,
+    fuels,
+    drivetrain,
+});
+
+struct_codec!(Fuel {
+    drive_type,
+    km_per_liter,
+    electric_consumption,
+    primary,
+});
+
+struct_codec!(Equipment { count, type_number });
+
+struct_codec!(EquipmentType {
+    number,
+    name,
+    shown_at_inspection,
+    shown_on_inquiry,
+    shown_on_standard_creation,
+});
+
+impl Encode for Catalogue {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for equipment_type in self.types() {
+            equipment_type.encode(out);
+        }
+    }
+}
+
+impl Decode for Catalogue {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let mut catalogue = Catalogue::new();
+        for equipment_type in Vec::<EquipmentType>::decode(input)? {
+            catalogue.insert(equipment_type);
+        }
+        Ok(catalogue)
+    }
+}
+
+struct_codec!(Inspection {
+    kind,
+    date,
+    result,
+    status,
+    status_date,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuel::DRIVETRAINS;
+
+    fn round_trip<T: Encode + Decode>(value: &T) -> T {
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes);
+        let mut input = bytes.as_slice();
+        let decoded = T::decode(&mut input).unwrap();
+        assert!(input.is_empty(), "{} bytes left over", input.len());
+        decoded
+    }
+
+    fn coded(number: u64, name: &str) -> Option<Coded> {
+        Some(Coded {
+            number: Some(number),
+            name: Some(name.to_string()),
+        })
+    }
+
+    fn timestamp(text: &str) -> Option<DateTime<FixedOffset>> {
+        Some(DateTime::parse_from_rfc3339(text).unwrap())
+    }
+
+    /// A record with every field set, each to a value of its own
+    fn full_record() -> VehicleRecord {
+        VehicleRecord {
+            ident: 1000000000000001,
+            kind: coded(1, "Personbil"),
+            usage: coded(2, "Privat personkørsel"),
+            plate: Some("AB12345".to_string()),
+            plate_class: Some(PlateClass::Standard),
+            plate_expiry: NaiveDate::from_ymd_opt(2031, 3, 31),
+            details: VehicleDetails {
+                created_from: Some("Typeattest".to_string()),
+                status: Some(Status::Other("Midlertidig".to_string())),
+                status_date: timestamp("2019-06-03T10:15:00+02:00"),
+                first_registration: NaiveDate::from_ymd_opt(2006, 5, 1),
+                vin: Some("WVWZZZ1KZ6W000001".to_string()),
+                vin_location: Some("Højre forhjulsophæng".to_string()),
+                model_year: Some(2006),
+                total_weight: Some(1900),
+                technical_total_weight: Some(1950),
+                curb_weight_min: Some(1300),
+                curb_weight_max: Some(1350),
+                train_weight: Some(3400),
+                axles: Some(2),
+                driving_axles: Some(1),
+                seats_min: Some(4),
+                seats_max: Some(5),
+                doors: Some(5),
+                coupling_possible: Some(true),
+                coupling_weight_unbraked: Some(650),
+                coupling_weight_braked: Some(1500),
+                ncap_test: Some(false),
+                condition: Some("Middel".to_string()),
+                taxi_suitable: Some(false),
+                traffic_damage: Some(true),
+                type_notification_number: Some("e1*2001/116*0242*24".to_string()),
+                type_approval_number: Some("9906131".to_string()),
+                comment: Some("Ombygget".to_string()),
+                designation: Designation {
+                    make: coded(3, "VW"),
+                    model: coded(4, "GOLF"),
+                    variant: coded(5, "1,9 TDI"),
+                    vehicle_type: coded(6, "1K"),
+                },
+                colour: coded(7, "Sølv"),
+                body_type: coded(8, "Hatchback"),
+                norm: coded(9, "Euro IV"),
+                environment: Environment {
+                    particle_filter: Some(true),
+                    co2_emission: Some(Decimal::new(1634, 1)),
+                },
+                motor: Motor {
+                    cylinders: Some(4),
+                    displacement: Some(Decimal::new(19680, 1)),
+                    displacement_unavailable: Some(false),
+                    max_power: Some(Decimal::new(-103, 0)),
+                    max_power_unavailable: Some(true),
+                    odometer: Some(312000),
+                    odometer_documented: Some(true),
+                    odometer_unavailable: Some(false),
+                    innovative_technology: Some(true),
+                    fuels: vec![Fuel {
+                        drive_type: coded(10, "Diesel").unwrap(),
+                        km_per_liter: Some(Decimal::new(20833, 3)),
+                        electric_consumption: Some(Decimal::new(0, 2)),
+                        primary: Some(true),
+                    }],
+                    drivetrain: Some(Drivetrain::PluginHybrid),
+                },
+                equipment: vec![Equipment {
+                    count: Some(2),
+                    type_number: Some(9901),
+                }],
+            },
+            inspection: Some(Inspection {
+                kind: Some("Periodisk syn".to_string()),
+                date: NaiveDate::from_ymd_opt(2024, 2, 29),
+                result: Some("Godkendt".to_string()),
+                status: Some("Aktiv".to_string()),
+                status_date: NaiveDate::from_ymd_opt(2024, 3, 1),
+            }),
+            registration_status: Some(Status::Deregistered),
+            registration_status_date: timestamp("2023-12-31T23:59:59-05:30"),
+        }
+    }
+
+    #[test]
+    fn full_record_round_trip() {
+        let record = full_record();
+        // A field added to the model has to be set here too
+        let debug = format!("{:?}", record);
+        assert!(!debug.contains("None"), "{}", debug);
+
+        let decoded = round_trip(&record);
+        assert_eq!(decoded, record);
+        // Offsets are kept, not only the instant
+        assert_eq!(
+            decoded.registration_status_date.unwrap().to_rfc3339(),
+            "2023-12-31T23:59:59-05:30"
+        );
+        assert_eq!(
+            round_trip(&VehicleRecord::default()),
+            VehicleRecord::default()
+        );
+    }
+
+    #[test]
+    fn every_variant_round_trip() {
+        for status in [
+            Status::Registered,
+            Status::Deregistered,
+            Status::Exported,
+            Status::Scrapped,
+            Status::Other("Midlertidig".to_string()),
+        ] {
+            assert_eq!(round_trip(&status), status);
+        }
+        for class in [
+            PlateClass::Standard,
+            PlateClass::Personalised,
+            PlateClass::Diplomatic,
+            PlateClass::Trade,
+            PlateClass::Historic,
+        ] {
+            assert_eq!(round_trip(&class), class);
+        }
+        for drivetrain in DRIVETRAINS {
+            assert_eq!(round_trip(drivetrain), *drivetrain);
+        }
+    }
+
+    #[test]
+    fn truncated_record_is_corrupt() {
+        let mut bytes = Vec::new();
+        full_record().encode(&mut bytes);
+        bytes.truncate(bytes.len() - 1);
+        let error = VehicleRecord::decode(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
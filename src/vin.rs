@@ -0,0 +1,326 @@
+//! Vehicle identification number (stelnummer) checks.
+//!
+//! Only 17 character ISO 3779 VINs are validated; DMR also holds older
+//! vehicles whose chassis numbers predate the standard.
+
+use std::fmt;
+
+/// Something wrong with a VIN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VinProblem {
+    Length(usize),
+    InvalidCharacter(char),
+    CheckDigit {
+        expected: char,
+        found: char,
+    },
+    ManufacturerMismatch {
+        wmi: &'static str,
+        registered: String,
+    },
+}
+
+impl fmt::Display for VinProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VinProblem::Length(len) => write!(f, "length is {} (expected 17)", len),
+            VinProblem::InvalidCharacter(c) => write!(f, "contains invalid character {:?}", c),
+            VinProblem::CheckDigit { expected, found } => {
+                write!(f, "check digit is {} (expected {})", found, expected)
+            }
+            VinProblem::ManufacturerMismatch { wmi, registered } => write!(
+                f,
+                "WMI belongs to {} but the vehicle is registered as {}",
+                wmi, registered
+            ),
+        }
+    }
+}
+
+/// World manufacturer identifiers and the DMR make names they stand for
+const WMI_TABLE: &[(&str, &str, &[&str])] = &[
+    ("WAU", "Audi", &["AUDI"]),
+    ("WUA", "Audi", &["AUDI"]),
+    ("TRU", "Audi", &["AUDI"]),
+    ("WBA", "BMW", &["BMW"]),
+    ("WBS", "BMW", &["BMW"]),
+    ("WBY", "BMW", &["BMW"]),
+    ("WMW", "Mini", &["MINI", "BMW"]),
+    ("WDB", "Mercedes-Benz", &["MERCEDES"]),
+    ("WDD", "Mercedes-Benz", &["MERCEDES"]),
+    ("WDC", "Mercedes-Benz", &["MERCEDES"]),
+    ("WDF", "Mercedes-Benz", &["MERCEDES"]),
+    ("W1K", "Mercedes-Benz", &["MERCEDES"]),
+    ("W1N", "Mercedes-Benz", &["MERCEDES"]),
+    ("W1V", "Mercedes-Benz", &["MERCEDES"]),
+    ("WME", "Smart", &["SMART", "MERCEDES"]),
+    ("WVW", "Volkswagen", &["VW", "VOLKSWAGEN"]),
+    ("WVG", "Volkswagen", &["VW", "VOLKSWAGEN"]),
+    ("WV1", "Volkswagen", &["VW", "VOLKSWAGEN"]),
+    ("WV2", "Volkswagen", &["VW", "VOLKSWAGEN"]),
+    ("WP0", "Porsche", &["PORSCHE"]),
+    ("WP1", "Porsche", &["PORSCHE"]),
+    ("W0L", "Opel", &["OPEL"]),
+    ("W0V", "Opel", &["OPEL"]),
+    ("WF0", "Ford", &["FORD"]),
+    ("WMA", "MAN", &["MAN"]),
+    ("TMB", "Skoda", &["SKODA"]),
+    ("VSS", "Seat", &["SEAT", "CUPRA"]),
+    ("VF1", "Renault", &["RENAULT"]),
+    ("VF3", "Peugeot", &["PEUGEOT"]),
+    ("VR3", "Peugeot", &["PEUGEOT"]),
+    ("VF7", "Citroen", &["CITROEN", "DS"]),
+    ("VR7", "Citroen", &["CITROEN", "DS"]),
+    ("UU1", "Dacia", &["DACIA"]),
+    ("ZFA", "Fiat", &["FIAT"]),
+    ("ZAR", "Alfa Romeo", &["ALFA"]),
+    ("YV1", "Volvo", &["VOLVO"]),
+    ("YV4", "Volvo", &["VOLVO"]),
+    ("YV2", "Volvo", &["VOLVO"]),
+    ("YS2", "Scania", &["SCANIA"]),
+    ("YS3", "Saab", &["SAAB"]),
+    ("XLR", "DAF", &["DAF"]),
+    ("SAL", "Land Rover", &["LAND ROVER", "LANDROVER"]),
+    ("SAJ", "Jaguar", &["JAGUAR"]),
+    ("JTD", "Toyota", &["TOYOTA"]),
+    ("JTE", "Toyota", &["TOYOTA"]),
+    ("JTM", "Toyota", &["TOYOTA"]),
+    ("JTN", "Toyota", &["TOYOTA"]),
+    ("SB1", "Toyota", &["TOYOTA"]),
+    ("VNK", "Toyota", &["TOYOTA"]),
+    ("NMT", "Toyota", &["TOYOTA"]),
+    ("JHM", "Honda", &["HONDA"]),
+    ("SHH", "Honda", &["HONDA"]),
+    ("JMZ", "Mazda", &["MAZDA"]),
+    ("JN1", "Nissan", &["NISSAN"]),
+    ("SJN", "Nissan", &["NISSAN"]),
+    ("VSK", "Nissan", &["NISSAN"]),
+    ("JMB", "Mitsubishi", &["MITSUBISHI"]),
+    ("JF1", "Subaru", &["SUBARU"]),
+    ("JSA", "Suzuki", &["SUZUKI"]),
+    ("TSM", "Suzuki", &["SUZUKI"]),
+    ("KNA", "Kia", &["KIA"]),
+    ("KNE", "Kia", &["KIA"]),
+    ("U5Y", "Kia", &["KIA"]),
+    ("KMH", "Hyundai", &["HYUNDAI"]),
+    ("TMA", "Hyundai", &["HYUNDAI"]),
+    ("NLH", "Hyundai", &["HYUNDAI"]),
+    ("5YJ", "Tesla", &["TESLA"]),
+    ("7SA", "Tesla", &["TESLA"]),
+    ("LRW", "Tesla", &["TESLA"]),
+    ("XP7", "Tesla", &["TESLA"]),
+    ("LPS", "Polestar", &["POLESTAR"]),
+    ("LSJ", "MG", &["MG"]),
+];
+
+/// VINs are stored and looked up without spaces and in upper case
+pub fn normalize(vin: &str) -> String {
+    vin.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Manufacturer named by the VIN's world manufacturer identifier, if known
+pub fn manufacturer(vin: &str) -> Option<&'static str> {
+    wmi_entry(vin).map(|(_, name, _)| *name)
+}
+
+fn wmi_entry(vin: &str) -> Option<&'static (&'static str, &'static str, &'static [&'static str])> {
+    let wmi = vin.get(..3)?;
+    WMI_TABLE.iter().find(|(code, _, _)| *code == wmi)
+}
+
+/// Check a VIN against ISO 3779 and, when `registered_make` is given,
+/// against the KoeretoejMaerkeTypeNavn it is registered under
+pub fn validate(vin: &str, registered_make: Option<&str>) -> Vec<VinProblem> {
+    let mut problems = Vec::new();
+
+    let len = vin.chars().count();
+    if len != 17 {
+        problems.push(VinProblem::Length(len));
+    }
+    if let Some(c) = vin.chars().find(|c| !is_vin_char(*c)) {
+        problems.push(VinProblem::InvalidCharacter(c));
+    }
+    if !problems.is_empty() {
+        return problems;
+    }
+
+    // The check digit is only mandatory for North American vehicles
+    if matches!(vin.as_bytes()[0], b'1'..=b'5') {
+        let expected = check_digit(vin);
+        let found = vin.as_bytes()[8] as char;
+        if expected != found {
+            problems.push(VinProblem::CheckDigit { expected, found });
+        }
+    }
+
+    if let (Some((_, name, makes)), Some(registered)) = (wmi_entry(vin), registered_make) {
+        let registered_norm = normalize_make(registered);
+        if !makes
+            .iter()
+            .any(|make| registered_norm.starts_with(&normalize_make(make)))
+        {
+            problems.push(VinProblem::ManufacturerMismatch {
+                wmi: name,
+                registered: registered.to_string(),
+            });
+        }
+    }
+
+    problems
+}
+
+/// A-Z and 0-9 except I, O and Q, which are too easily confused with 1 and 0
+fn is_vin_char(c: char) -> bool {
+    (c.is_ascii_uppercase() && !matches!(c, 'I' | 'O' | 'Q')) || c.is_ascii_digit()
+}
+
+/// Compute the position 9 check digit of a valid 17 character VIN
+fn check_digit(vin: &str) -> char {
+    const WEIGHTS: [u32; 17] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+    let sum: u32 = vin
+        .bytes()
+        .zip(WEIGHTS)
+        .map(|(b, weight)| transliterate(b) * weight)
+        .sum();
+    match sum % 11 {
+        10 => 'X',
+        digit => char::from_digit(digit, 10).unwrap(),
+    }
+}
+
+fn transliterate(b: u8) -> u32 {
+    match b {
+        b'0'..=b'9' => (b - b'0') as u32,
+        b'A' | b'J' => 1,
+        b'B' | b'K' | b'S' => 2,
+        b'C' | b'L' | b'T' => 3,
+        b'D' | b'M' | b'U' => 4,
+        b'E' | b'N' | b'V' => 5,
+        b'F' | b'W' => 6,
+        b'G' | b'P' | b'X' => 7,
+        b'H' | b'Y' => 8,
+        b'R' | b'Z' => 9,
+        _ => 0,
+    }
+}
+
+/// Upper case letters and digits only, with diacritics folded, so that
+/// "Citroën" matches "CITROEN" and "Mercedes-Benz" matches "MERCEDES"
+fn normalize_make(make: &str) -> String {
+    make.chars()
+        .flat_map(char::to_uppercase)
+        .map(|c| match c {
+            'Ë' | 'É' | 'È' => 'E',
+            'Ö' | 'Ø' => 'O',
+            'Ä' | 'Å' | 'Æ' => 'A',
+            'Ü' => 'U',
+            c => c,
+        })
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The worked example of the North American check digit
+    const NORTH_AMERICAN: &str = "1M8GDM9AXKP042788";
+
+    #[test]
+    fn transliteration() {
+        let values: Vec<u32> = b"0123456789ABCDEFGHJKLMNPRSTUVWXYZ"
+            .iter()
+            .map(|b| transliterate(*b))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 7, 9, 2, 3, 4,
+                5, 6, 7, 8, 9
+            ]
+        );
+    }
+
+    #[test]
+    fn check_digits() {
+        assert_eq!(check_digit(NORTH_AMERICAN), 'X');
+        assert_eq!(check_digit("11111111111111111"), '1');
+        assert_eq!(validate(NORTH_AMERICAN, None), []);
+
+        let wrong = NORTH_AMERICAN.replace("AX", "A7");
+        assert_eq!(
+            validate(&wrong, None),
+            [VinProblem::CheckDigit {
+                expected: 'X',
+                found: '7'
+            }]
+        );
+    }
+
+    #[test]
+    fn check_digit_only_for_north_america() {
+        // Position 9 of a European VIN is not a check digit
+        assert_eq!(validate("WAUZZZ4F38N069602", Some("AUDI")), []);
+        assert_eq!(validate("VF7SA8FP0AW123456", None), []);
+        assert_eq!(validate("JTDKB20U503123456", None), []);
+        // but it is for a Tesla built in the US
+        assert_eq!(
+            validate("5YJ3E1EA0KF123456", Some("TESLA")),
+            [VinProblem::CheckDigit {
+                expected: '6',
+                found: '0'
+            }]
+        );
+    }
+
+    #[test]
+    fn manufacturer_mismatch() {
+        assert_eq!(manufacturer("WAUZZZ4F38N069602"), Some("Audi"));
+        assert_eq!(manufacturer("ABCZZZ4F38N069602"), None);
+        assert_eq!(
+            validate("WAUZZZ4F38N069602", Some("VW")),
+            [VinProblem::ManufacturerMismatch {
+                wmi: "Audi",
+                registered: "VW".to_string()
+            }]
+        );
+        // Unknown WMIs and unknown makes are not compared
+        assert_eq!(validate("ABCZZZ4F38N069602", Some("VW")), []);
+        assert_eq!(validate("WAUZZZ4F38N069602", None), []);
+    }
+
+    #[test]
+    fn makes_are_compared_without_diacritics() {
+        assert_eq!(normalize_make("Citroën"), "CITROEN");
+        assert_eq!(normalize_make("Mercedes-Benz"), "MERCEDESBENZ");
+        assert_eq!(normalize_make("Ford Østrig"), "FORDOSTRIG");
+        assert_eq!(validate("VF7SA8FP0AW123456", Some("Citroën")), []);
+        assert_eq!(validate("WDD2050071F123456", Some("Mercedes-Benz")), []);
+        assert_eq!(validate("WMWXM710X0T123456", Some("Mini")), []);
+        assert_eq!(validate("YS2R4X20005123456", Some("Scania Danmark")), []);
+    }
+
+    #[test]
+    fn length_and_characters() {
+        assert_eq!(validate("WAUZZZ4F38N06960", None), [VinProblem::Length(16)]);
+        // Counted in characters, not bytes
+        assert_eq!(
+            validate("WAUZZZ4F38N06960Ø", None),
+            [VinProblem::InvalidCharacter('Ø')]
+        );
+        assert_eq!(
+            validate("WAUZZZ4F38N0696O2", None),
+            [VinProblem::InvalidCharacter('O')]
+        );
+        assert_eq!(
+            validate("wauzzz4f38n069602", None),
+            [VinProblem::InvalidCharacter('w')]
+        );
+        assert_eq!(normalize(" wauzzz 4f38n069602 "), "WAUZZZ4F38N069602");
+    }
+}